@@ -1,25 +1,83 @@
-use crate::get_id;
-use smt2parser::concrete::{Sort, Symbol};
+use smt2parser::concrete::{Sort, Symbol, Term};
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::slice::Iter;
 
-struct Signature {
-    parameters: Vec<Sort>,
-    result: Sort,
+/// The sort of a declared function.
+pub(crate) struct Signature {
+    pub parameters: Vec<Sort>,
+    pub result: Sort,
 }
 
+/// A `define-fun` macro.
+#[derive(Clone)]
+pub(crate) struct Definition {
+    pub parameters: Vec<(Symbol, Sort)>,
+    pub result: Sort,
+    pub body: Term,
+    /// The encoding of a definition without parameters, made once when it is defined.
+    pub literal: Option<Literal>,
+}
+
+#[derive(Default)]
 pub(crate) struct AssertionSet {
     uninterpreted_functions: HashMap<usize, Signature>,
-    symbol_table: HashMap<usize, Symbol>,
-    symbol_table_rev: HashMap<Symbol, usize>,
+    /// The id of each symbol declared in this set; `VariableManager` maps ids back.
+    symbol_table: HashMap<Symbol, usize>,
+    definitions: HashMap<Symbol, Definition>,
+    /// The literal of each gate encoded in this set, keyed by its operator and
+    /// normalized arguments.
+    gates: HashMap<(&'static str, Vec<Literal>), Literal>,
     clauses: Vec<Clause>,
+    /// Named assertions with the selector literal guarding each.
+    named: Vec<(Symbol, Literal)>,
+    /// The asserted terms, as parsed.
+    assertions: Vec<Term>,
+    /// Guards the clauses of a pushed level in the persistent solver of `Context`,
+    /// which assumes it while the level is on the stack.
+    activation: Option<Literal>,
+    /// The number of clauses already given to the persistent solver.
+    given: usize,
 }
 
 impl AssertionSet {
+    /// The set of a pushed level, guarded by `activation`.
+    pub fn with_activation(activation: Literal) -> Self {
+        Self {
+            activation: Some(activation),
+            ..Default::default()
+        }
+    }
+
     pub fn add_clauses(&mut self, clauses: Vec<Clause>) {
         self.clauses.extend(clauses);
     }
+
+    pub fn activation(&self) -> Option<Literal> {
+        self.activation
+    }
+
+    /// The clauses not given to the persistent solver yet, guarded by the activation literal.
+    pub fn take_new_clauses(&mut self) -> Vec<Clause> {
+        let new = self.clauses[self.given..]
+            .iter()
+            .map(|clause| {
+                let mut clause = clause.clone();
+                clause.literals.extend(self.activation.map(|a| a.not()));
+                clause
+            })
+            .collect();
+        self.given = self.clauses.len();
+        new
+    }
+
+    /// The unit clause disabling this level in the persistent solver, once popped.
+    /// `None` if the solver never saw its clauses.
+    pub fn deactivation(&self) -> Option<Clause> {
+        self.activation
+            .filter(|_| self.given > 0)
+            .map(|a| Clause::new(vec![a.not()]))
+    }
 }
 
 #[derive(Default, Clone, Eq, PartialEq)]
@@ -49,7 +107,7 @@ impl Clause {
             .literals
             .iter()
             .map(|l| {
-                let mut new_l = l.clone();
+                let mut new_l = *l;
                 new_l.id = ids[l.id];
                 new_l
             })
@@ -75,35 +133,40 @@ impl Debug for Literal {
     }
 }
 
-pub fn and(args: Vec<Literal>, clauses: &mut Vec<Clause>) -> Literal {
-    let literal = Literal::new(get_id());
-    args.into_iter()
-        .for_each(|l| clauses.push(Clause::new(vec![literal.not(), l])));
-    literal
-}
-
-pub fn implication(args: Vec<Literal>, clauses: &mut Vec<Clause>) -> Literal {
-    let literal = Literal::new(get_id());
-    clauses.push(Clause::new(vec![literal.not(), args[0].not(), args[1]]));
-    literal
+/// Tseitin encoding: each gate defines the fresh `literal` as equivalent to the connective
+/// applied to `args`, by clauses for both directions of the equivalence.
+pub fn and(literal: Literal, args: Vec<Literal>, clauses: &mut Vec<Clause>) {
+    let mut all = vec![literal];
+    for l in args {
+        clauses.push(Clause::new(vec![literal.not(), l]));
+        all.push(l.not());
+    }
+    clauses.push(Clause::new(all));
 }
 
-pub fn equality(args: Vec<Literal>, clauses: &mut Vec<Clause>) -> Literal {
-    let backward = implication(vec![args[1], args[0]], clauses);
-    let forward = implication(args, clauses);
-    and(vec![forward, backward], clauses)
+pub fn equality(literal: Literal, args: Vec<Literal>, clauses: &mut Vec<Clause>) {
+    let (a, b) = (args[0], args[1]);
+    clauses.push(Clause::new(vec![literal.not(), a.not(), b]));
+    clauses.push(Clause::new(vec![literal.not(), a, b.not()]));
+    clauses.push(Clause::new(vec![literal, a, b]));
+    clauses.push(Clause::new(vec![literal, a.not(), b.not()]));
 }
 
-pub fn xor(mut args: Vec<Literal>, clauses: &mut Vec<Clause>) -> Literal {
-    args[0] = args[0].not();
-    equality(args, clauses)
+pub fn or(literal: Literal, args: Vec<Literal>, clauses: &mut Vec<Clause>) {
+    let mut any = vec![literal.not()];
+    for l in args {
+        clauses.push(Clause::new(vec![literal, l.not()]));
+        any.push(l);
+    }
+    clauses.push(Clause::new(any));
 }
 
-pub fn or(mut args: Vec<Literal>, clauses: &mut Vec<Clause>) -> Literal {
-    let literal = Literal::new(get_id());
-    args.push(literal.not());
-    clauses.push(Clause::new(args));
-    literal
+pub fn ite(literal: Literal, args: Vec<Literal>, clauses: &mut Vec<Clause>) {
+    let (condition, then, otherwise) = (args[0], args[1], args[2]);
+    clauses.push(Clause::new(vec![literal.not(), condition.not(), then]));
+    clauses.push(Clause::new(vec![literal.not(), condition, otherwise]));
+    clauses.push(Clause::new(vec![literal, condition.not(), then.not()]));
+    clauses.push(Clause::new(vec![literal, condition, otherwise.not()]));
 }
 
 impl Literal {
@@ -129,30 +192,56 @@ impl AssertionSet {
             .insert(symbol_id, Signature { parameters, result });
     }
 
+    pub fn get_signature(&self, symbol_id: usize) -> Option<&Signature> {
+        self.uninterpreted_functions.get(&symbol_id)
+    }
+
     pub fn get_id(&self, symbol: &Symbol) -> Option<usize> {
-        self.symbol_table_rev.get(&symbol).map(|&id| id)
+        self.symbol_table.get(symbol).copied()
     }
 
     pub fn set_id(&mut self, symbol: Symbol, id: usize) {
-        assert!(
-            !self.symbol_table.contains_key(&id) && !self.symbol_table_rev.contains_key(&symbol)
-        );
-        self.symbol_table.insert(id, symbol.clone());
-        self.symbol_table_rev.insert(symbol, id);
+        assert!(self.symbol_table.insert(symbol, id).is_none());
+    }
+
+    pub fn add_definition(&mut self, name: Symbol, definition: Definition) {
+        self.definitions.insert(name, definition);
+    }
+
+    pub fn get_definition(&self, name: &Symbol) -> Option<&Definition> {
+        self.definitions.get(name)
     }
 
-    pub fn get_clauses(&self) -> Iter<Clause> {
+    pub fn add_gate(&mut self, gate: (&'static str, Vec<Literal>), literal: Literal) {
+        self.gates.insert(gate, literal);
+    }
+
+    pub fn get_gate(&self, gate: &(&'static str, Vec<Literal>)) -> Option<Literal> {
+        self.gates.get(gate).copied()
+    }
+
+    pub fn get_clauses(&self) -> Iter<'_, Clause> {
         self.clauses.iter()
     }
-}
 
-impl Default for AssertionSet {
-    fn default() -> Self {
-        Self {
-            uninterpreted_functions: HashMap::new(),
-            symbol_table: Default::default(),
-            symbol_table_rev: Default::default(),
-            clauses: vec![],
-        }
+    pub fn add_named(&mut self, name: Symbol, selector: Literal) {
+        self.named.push((name, selector));
+    }
+
+    pub fn get_named(&self) -> Iter<'_, (Symbol, Literal)> {
+        self.named.iter()
+    }
+
+    /// The ids of the symbols declared in this set.
+    pub fn get_symbol_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.symbol_table.values().copied()
+    }
+
+    pub fn add_assertion(&mut self, term: Term) {
+        self.assertions.push(term);
+    }
+
+    pub fn get_assertions(&self) -> Iter<'_, Term> {
+        self.assertions.iter()
     }
 }
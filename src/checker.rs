@@ -0,0 +1,594 @@
+use crate::clause_db::{ClauseArena, ClauseRef, Lit};
+use crate::proof::ProofFormat;
+use itertools::Itertools;
+use log::warn;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Why a proof was rejected. `line` is the line of the offending step in a text proof,
+/// and its position (1-based) in a binary proof.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn failure(line: usize, message: impl Into<String>) -> Failure {
+    Failure {
+        line,
+        message: message.into(),
+    }
+}
+
+/// A DIMACS CNF: the number of variables declared by its header, and its clauses.
+pub struct Cnf {
+    pub variables: usize,
+    pub clauses: Vec<Vec<Lit>>,
+}
+
+impl Cnf {
+    /// The literal `number` on `line`, whose variable must be declared by the header.
+    fn lit(&self, number: i64, line: usize) -> Result<Lit, Failure> {
+        let variable = number.unsigned_abs();
+        if variable > self.variables as u64 {
+            return Err(failure(
+                line,
+                format!(
+                    "variable {} beyond the {} variables of the CNF",
+                    variable, self.variables
+                ),
+            ));
+        }
+        Ok(Lit::new(variable as usize, number > 0))
+    }
+}
+
+/// A clause as written in DIMACS, with its terminating 0.
+fn display(literals: &[Lit]) -> String {
+    literals
+        .iter()
+        .map(|l| {
+            let sign = if l.value() { "" } else { "-" };
+            format!("{}{}", sign, l.variable())
+        })
+        .chain(std::iter::once("0".to_string()))
+        .join(" ")
+}
+
+/// Parses DIMACS CNF. Its literals must come after the `p cnf` header and use only the
+/// variables it declares.
+pub fn parse_dimacs(input: &str) -> Result<Cnf, Failure> {
+    let mut cnf = None;
+    let mut clause = vec![];
+    let mut last_line = 0;
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+        if line.starts_with('p') {
+            let variables = match line.split_whitespace().collect_vec()[..] {
+                ["p", "cnf", variables, _] => variables.parse().ok(),
+                _ => None,
+            }
+            .filter(|&variables| variables <= Lit::MAX_VARIABLE)
+            .ok_or_else(|| failure(index + 1, format!("invalid header {}", line)))?;
+            cnf = Some(Cnf {
+                variables,
+                clauses: vec![],
+            });
+            continue;
+        }
+        last_line = index + 1;
+        let cnf = cnf
+            .as_mut()
+            .ok_or_else(|| failure(index + 1, "missing p cnf header"))?;
+        for token in line.split_whitespace() {
+            let number: i64 = token
+                .parse()
+                .map_err(|_| failure(index + 1, format!("invalid literal {}", token)))?;
+            if number == 0 {
+                cnf.clauses.push(std::mem::take(&mut clause));
+            } else {
+                clause.push(cnf.lit(number, index + 1)?);
+            }
+        }
+    }
+    if !clause.is_empty() {
+        return Err(failure(last_line, "missing terminating 0"));
+    }
+    cnf.ok_or_else(|| failure(input.lines().count(), "missing p cnf header"))
+}
+
+/// One step of a proof: its line and its numbers, without the terminating zeros.
+struct Line {
+    line: usize,
+    delete: bool,
+    numbers: Vec<i64>,
+}
+
+fn parse_text(input: &str, lrat: bool) -> Result<Vec<Line>, Failure> {
+    let mut lines = vec![];
+    for (index, text) in input.lines().enumerate() {
+        let mut tokens: Vec<_> = text.split_whitespace().collect();
+        if tokens.is_empty() || tokens[0] == "c" {
+            continue;
+        }
+        // DRAT deletions start with `d`, LRAT ones have it after the clause id.
+        let position = if lrat { 1 } else { 0 };
+        let delete = tokens.get(position) == Some(&"d");
+        if delete {
+            tokens.remove(position);
+        }
+        let mut numbers = tokens
+            .iter()
+            .map(|t| t.parse())
+            .collect::<Result<Vec<i64>, _>>()
+            .map_err(|_| failure(index + 1, format!("invalid step {}", text)))?;
+        if numbers.pop() != Some(0) {
+            return Err(failure(index + 1, "missing terminating 0"));
+        }
+        lines.push(Line {
+            line: index + 1,
+            delete,
+            numbers,
+        });
+    }
+    Ok(lines)
+}
+
+/// Binary steps are `a` or `d` followed by numbers encoded as `2 * |n| + sign`
+/// in 7-bit groups. An LRAT addition holds two zero-terminated lists.
+fn parse_binary(input: &[u8], lrat: bool) -> Result<Vec<Line>, Failure> {
+    let mut lines = vec![];
+    let mut position = 0;
+    while position < input.len() {
+        let line = lines.len() + 1;
+        let delete = match input[position] {
+            b'a' => false,
+            b'd' => true,
+            tag => return Err(failure(line, format!("invalid step tag {:#x}", tag))),
+        };
+        position += 1;
+        let mut zeros = if lrat && !delete { 2 } else { 1 };
+        let mut numbers = vec![];
+        while zeros > 0 {
+            let mut encoded: u64 = 0;
+            let mut shift = 0;
+            loop {
+                let &byte = input
+                    .get(position)
+                    .ok_or_else(|| failure(line, "truncated step"))?;
+                position += 1;
+                encoded |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let number = (encoded >> 1) as i64;
+            let number = if encoded & 1 == 1 { -number } else { number };
+            if number == 0 {
+                zeros -= 1;
+            }
+            numbers.push(number);
+        }
+        numbers.pop();
+        lines.push(Line {
+            line,
+            delete,
+            numbers,
+        });
+    }
+    Ok(lines)
+}
+
+fn parse_proof(input: &[u8], format: ProofFormat, lrat: bool) -> Result<Vec<Line>, Failure> {
+    match format {
+        ProofFormat::TEXT => parse_text(&String::from_utf8_lossy(input), lrat),
+        ProofFormat::BINARY => parse_binary(input, lrat),
+    }
+}
+
+/// Clauses and unit propagation under a temporary assignment, shared by both checkers.
+#[derive(Default)]
+struct Propagator {
+    arena: ClauseArena,
+    clauses: Vec<ClauseRef>,
+    active: Vec<bool>,
+    values: Vec<Option<bool>>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// Clauses watching each literal, indexed by `Lit::index`, by clause index.
+    watcher: Vec<Vec<usize>>,
+    units: Vec<usize>,
+}
+
+impl Propagator {
+    fn add(&mut self, literals: &[Lit]) -> usize {
+        let index = self.clauses.len();
+        self.add_variables(literals);
+        let clause = self.arena.alloc(literals, false, 0);
+        match literals.len() {
+            0 => {}
+            1 => self.units.push(index),
+            _ => {
+                self.watcher[literals[0].index()].push(index);
+                self.watcher[literals[1].index()].push(index);
+            }
+        }
+        self.clauses.push(clause);
+        self.active.push(true);
+        index
+    }
+
+    fn add_variables(&mut self, literals: &[Lit]) {
+        if let Some(variable) = literals.iter().map(|l| l.variable()).max() {
+            if variable >= self.values.len() {
+                self.values.resize(variable + 1, None);
+                self.reasons.resize(variable + 1, None);
+                self.watcher.resize(2 * variable + 2, vec![]);
+            }
+        }
+    }
+
+    fn literals(&self, index: usize) -> Vec<Lit> {
+        self.arena.literals(self.clauses[index]).collect()
+    }
+
+    fn value(&self, literal: Lit) -> Option<bool> {
+        self.values
+            .get(literal.variable())
+            .copied()
+            .flatten()
+            .map(|v| v == literal.value())
+    }
+
+    /// Assigns `literal`; false if it is already false.
+    fn assign(&mut self, literal: Lit, reason: Option<usize>) -> bool {
+        match self.value(literal) {
+            Some(value) => value,
+            None => {
+                self.values[literal.variable()] = Some(literal.value());
+                self.reasons[literal.variable()] = reason;
+                self.trail.push(literal);
+                true
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for l in self.trail.drain(..) {
+            self.values[l.variable()] = None;
+            self.reasons[l.variable()] = None;
+        }
+    }
+
+    /// Propagates the active clauses from the assignments of the trail, which are
+    /// the negation of the lemma to check. Returns the conflicting clause, if any.
+    fn propagate(&mut self) -> Option<usize> {
+        for i in 0..self.units.len() {
+            let index = self.units[i];
+            let literal = self.arena.lit(self.clauses[index], 0);
+            if self.active[index] && !self.assign(literal, Some(index)) {
+                return Some(index);
+            }
+        }
+        let mut head = 0;
+        while head < self.trail.len() {
+            let false_literal = !self.trail[head];
+            head += 1;
+            let watching = std::mem::take(&mut self.watcher[false_literal.index()]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+            for (position, &index) in watching.iter().enumerate() {
+                let clause = self.clauses[index];
+                if self.arena.lit(clause, 0) == false_literal {
+                    self.arena.swap(clause, 0, 1);
+                }
+                let other = self.arena.lit(clause, 0);
+                if !self.active[index] || self.value(other) == Some(true) {
+                    kept.push(index);
+                    continue;
+                }
+                let replacement = (2..self.arena.len(clause))
+                    .find(|&i| self.value(self.arena.lit(clause, i)) != Some(false));
+                if let Some(i) = replacement {
+                    self.arena.swap(clause, 1, i);
+                    self.watcher[self.arena.lit(clause, 1).index()].push(index);
+                    continue;
+                }
+                kept.push(index);
+                if !self.assign(other, Some(index)) {
+                    kept.extend_from_slice(&watching[position + 1..]);
+                    conflict = Some(index);
+                    break;
+                }
+            }
+            self.watcher[false_literal.index()] = kept;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// The clauses taking part in the derivation of `conflict`.
+    fn antecedents(&self, conflict: usize) -> Vec<usize> {
+        let mut seen = vec![false; self.values.len()];
+        let mut antecedents = vec![conflict];
+        let mut pending = vec![conflict];
+        while let Some(index) = pending.pop() {
+            for l in self.arena.literals(self.clauses[index]) {
+                if std::mem::replace(&mut seen[l.variable()], true) {
+                    continue;
+                }
+                if let Some(reason) = self.reasons[l.variable()] {
+                    antecedents.push(reason);
+                    pending.push(reason);
+                }
+            }
+        }
+        antecedents
+    }
+}
+
+/// Backward DRAT checking: the proof is replayed up to its empty clause, then the lemmas
+/// the refutation depends on are checked in reverse order, by reverse unit propagation
+/// or else as resolution asymmetric tautologies on their first literal.
+/// Returns the earliest failing lemma.
+pub fn check_drat(cnf: &Cnf, proof: &[u8], format: ProofFormat) -> Result<(), Failure> {
+    let lines = parse_proof(proof, format, false)?;
+    let mut propagator = Propagator::default();
+    let mut by_literals: HashMap<Vec<Lit>, Vec<usize>> = HashMap::new();
+    let key = |literals: &[Lit]| {
+        literals
+            .iter()
+            .copied()
+            .sorted_by_key(|l| l.index())
+            .collect()
+    };
+    for clause in &cnf.clauses {
+        let index = propagator.add(clause);
+        by_literals.entry(key(clause)).or_default().push(index);
+    }
+    if cnf.clauses.iter().any(Vec::is_empty) {
+        return Ok(());
+    }
+    let last_line = lines.last().map_or(0, |line| line.line);
+    // (clause, line, deleted, literals) for each step before the empty clause. Literals
+    // are kept in proof order, as propagation reorders them in the arena and the first
+    // one is the RAT pivot.
+    let mut steps = vec![];
+    let mut refutation = None;
+    for line in lines {
+        let literals = line
+            .numbers
+            .iter()
+            .map(|&n| cnf.lit(n, line.line))
+            .collect::<Result<Vec<_>, _>>()?;
+        if line.delete {
+            match by_literals.get_mut(&key(&literals)).and_then(Vec::pop) {
+                Some(index) => {
+                    propagator.active[index] = false;
+                    steps.push((index, line.line, true, literals));
+                }
+                None => warn!(
+                    "line {}: ignoring deletion of missing clause {}",
+                    line.line,
+                    display(&literals)
+                ),
+            }
+        } else {
+            let index = propagator.add(&literals);
+            by_literals.entry(key(&literals)).or_default().push(index);
+            let empty = literals.is_empty();
+            steps.push((index, line.line, false, literals));
+            if empty {
+                refutation = Some(index);
+                break;
+            }
+        }
+    }
+    let Some(refutation) = refutation else {
+        return Err(failure(
+            last_line,
+            "the proof does not derive the empty clause",
+        ));
+    };
+    let mut core = vec![false; propagator.clauses.len()];
+    core[refutation] = true;
+    let mut first_failure = None;
+    for (index, line, deleted, lemma) in steps.into_iter().rev() {
+        propagator.active[index] = deleted;
+        if deleted || !core[index] {
+            continue;
+        }
+        match redundant(&mut propagator, &lemma) {
+            Some(antecedents) => antecedents.into_iter().for_each(|c| core[c] = true),
+            None => {
+                first_failure = Some(failure(
+                    line,
+                    format!("lemma {} is neither RUP nor RAT", display(&lemma)),
+                ))
+            }
+        }
+    }
+    first_failure.map_or(Ok(()), Err)
+}
+
+/// Checks `lemma` by RUP, then by RAT on its first literal. Returns the clauses used.
+fn redundant(propagator: &mut Propagator, lemma: &[Lit]) -> Option<Vec<usize>> {
+    if let Some(antecedents) = rup(propagator, lemma) {
+        return Some(antecedents);
+    }
+    let &pivot = lemma.first()?;
+    let candidates: Vec<_> = (0..propagator.clauses.len())
+        .filter(|&c| propagator.active[c] && propagator.literals(c).contains(&!pivot))
+        .collect();
+    let mut used = vec![];
+    for candidate in candidates {
+        let mut resolvent = lemma.to_vec();
+        resolvent.extend(
+            propagator
+                .literals(candidate)
+                .into_iter()
+                .filter(|&l| l != !pivot),
+        );
+        used.push(candidate);
+        used.extend(rup(propagator, &resolvent)?);
+    }
+    Some(used)
+}
+
+/// Reverse unit propagation: `lemma` is implied if its negation propagates to a conflict.
+/// Returns the clauses used.
+fn rup(propagator: &mut Propagator, lemma: &[Lit]) -> Option<Vec<usize>> {
+    propagator.add_variables(lemma);
+    let tautology = !lemma.iter().all(|&l| propagator.assign(!l, None));
+    let antecedents = if tautology {
+        Some(vec![])
+    } else {
+        propagator.propagate().map(|c| propagator.antecedents(c))
+    };
+    propagator.reset();
+    antecedents
+}
+
+/// Forward LRAT checking: each lemma must become conflicting by propagating its hints
+/// in order, and RAT lemmas list every clause containing the negation of their first
+/// literal as a negative hint, followed by the hints of that resolvent.
+pub fn check_lrat(cnf: &Cnf, proof: &[u8], format: ProofFormat) -> Result<(), Failure> {
+    let lines = parse_proof(proof, format, true)?;
+    let last_line = lines.last().map_or(0, |line| line.line);
+    let mut propagator = Propagator::default();
+    let mut ids: HashMap<i64, usize> = HashMap::new();
+    for (i, clause) in cnf.clauses.iter().enumerate() {
+        ids.insert(i as i64 + 1, propagator.add(clause));
+    }
+    if cnf.clauses.iter().any(Vec::is_empty) {
+        return Ok(());
+    }
+    for line in lines {
+        if line.delete {
+            // `id d ids`; binary deletions have no leading id.
+            let skip = match format {
+                ProofFormat::TEXT => 1,
+                ProofFormat::BINARY => 0,
+            };
+            for id in line.numbers.iter().skip(skip) {
+                if let Some(index) = ids.remove(id) {
+                    propagator.active[index] = false;
+                }
+            }
+            continue;
+        }
+        let (&id, rest) = line
+            .numbers
+            .split_first()
+            .ok_or_else(|| failure(line.line, "missing clause id"))?;
+        let split = rest.iter().position(|&n| n == 0).unwrap_or(rest.len());
+        let lemma = rest[..split]
+            .iter()
+            .map(|&n| cnf.lit(n, line.line))
+            .collect::<Result<Vec<_>, _>>()?;
+        let hints = rest.get(split + 1..).unwrap_or(&[]);
+        check_hints(&mut propagator, &ids, &lemma, hints).map_err(|message| {
+            failure(line.line, format!("lemma {}: {}", display(&lemma), message))
+        })?;
+        if lemma.is_empty() {
+            return Ok(());
+        }
+        ids.insert(id, propagator.add(&lemma));
+    }
+    Err(failure(
+        last_line,
+        "the proof does not derive the empty clause",
+    ))
+}
+
+fn check_hints(
+    propagator: &mut Propagator,
+    ids: &HashMap<i64, usize>,
+    lemma: &[Lit],
+    hints: &[i64],
+) -> Result<(), String> {
+    let split = hints.iter().position(|&h| h < 0).unwrap_or(hints.len());
+    let (common, rat) = hints.split_at(split);
+    if rat.is_empty() {
+        return follow_hints(propagator, ids, lemma, common);
+    }
+    let Some(&pivot) = lemma.first() else {
+        return Err("RAT hints on an empty lemma".to_string());
+    };
+    let mut groups: HashMap<i64, &[i64]> = HashMap::new();
+    let mut rest = rat;
+    while let Some((&negative, tail)) = rest.split_first() {
+        let end = tail.iter().position(|&h| h < 0).unwrap_or(tail.len());
+        groups.insert(-negative, &tail[..end]);
+        rest = &tail[end..];
+    }
+    for (&id, &index) in ids {
+        let literals = propagator.literals(index);
+        if !propagator.active[index] || !literals.contains(&!pivot) {
+            continue;
+        }
+        let group = groups
+            .get(&id)
+            .ok_or_else(|| format!("no RAT hints for clause {}", id))?;
+        let mut resolvent = lemma.to_vec();
+        resolvent.extend(literals.into_iter().filter(|&l| l != !pivot));
+        let hints: Vec<_> = common.iter().chain(group.iter()).copied().collect();
+        follow_hints(propagator, ids, &resolvent, &hints)?;
+    }
+    Ok(())
+}
+
+/// Assigns the negation of `lemma`, then requires every hint to be unit, until one conflicts.
+fn follow_hints(
+    propagator: &mut Propagator,
+    ids: &HashMap<i64, usize>,
+    lemma: &[Lit],
+    hints: &[i64],
+) -> Result<(), String> {
+    propagator.add_variables(lemma);
+    let tautology = !lemma.iter().all(|&l| propagator.assign(!l, None));
+    let result = if tautology {
+        Ok(())
+    } else {
+        unit_chain(propagator, ids, hints)
+    };
+    propagator.reset();
+    result
+}
+
+fn unit_chain(
+    propagator: &mut Propagator,
+    ids: &HashMap<i64, usize>,
+    hints: &[i64],
+) -> Result<(), String> {
+    for hint in hints {
+        let &index = ids
+            .get(hint)
+            .ok_or_else(|| format!("unknown hint {}", hint))?;
+        let literals = propagator.literals(index);
+        let open: Vec<_> = literals
+            .into_iter()
+            .filter(|&l| propagator.value(l) != Some(false))
+            .collect();
+        match open[..] {
+            [] => return Ok(()),
+            [unit] if propagator.value(unit).is_none() => {
+                propagator.assign(unit, Some(index));
+            }
+            _ => return Err(format!("hint {} is not unit", hint)),
+        }
+    }
+    Err("the hints do not lead to a conflict".to_string())
+}
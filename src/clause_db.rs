@@ -0,0 +1,310 @@
+use crate::assertion_set::Literal;
+use std::collections::HashMap;
+use std::ops::Not;
+
+const FIRST_REDUCTION: u64 = 2000;
+const REDUCTION_INCREMENT: u64 = 300;
+const ACTIVITY_DECAY: f32 = 0.999;
+
+/// A literal encoded as `2 * variable + sign`, the sign bit being set for negative literals.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Lit(u32);
+
+impl Lit {
+    /// The largest variable that fits in the encoding.
+    pub const MAX_VARIABLE: usize = (u32::MAX >> 1) as usize;
+
+    pub fn new(variable: usize, value: bool) -> Self {
+        assert!(
+            variable <= Self::MAX_VARIABLE,
+            "variable {} out of range",
+            variable
+        );
+        Lit(((variable as u32) << 1) | !value as u32)
+    }
+
+    pub fn variable(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn value(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    /// Dense index for per-literal tables.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Lit(self.0 ^ 1)
+    }
+}
+
+impl From<Literal> for Lit {
+    fn from(literal: Literal) -> Self {
+        Lit::new(literal.id, literal.value)
+    }
+}
+
+impl From<Lit> for Literal {
+    fn from(lit: Lit) -> Self {
+        Literal {
+            value: lit.value(),
+            id: lit.variable(),
+        }
+    }
+}
+
+/// Offset of a clause header in the `ClauseArena`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ClauseRef(u32);
+
+const HEADER: usize = 3;
+const LEARNED: u32 = 1;
+const DELETED: u32 = 1 << 1;
+const USED: u32 = 1 << 2;
+const LBD_SHIFT: u32 = 3;
+
+/// Clauses stored contiguously in one `u32` buffer. Each clause is a header of
+/// `[size, flags | lbd << 3, activity]` followed by its literals.
+/// The first two literals of a clause are its watches.
+#[derive(Default)]
+pub struct ClauseArena {
+    data: Vec<u32>,
+    wasted: usize,
+}
+
+impl ClauseArena {
+    pub fn alloc(&mut self, literals: &[Lit], learned: bool, lbd: usize) -> ClauseRef {
+        let clause = ClauseRef(self.data.len() as u32);
+        let flags = if learned { LEARNED | USED } else { 0 };
+        self.data.push(literals.len() as u32);
+        self.data.push(flags | (lbd as u32) << LBD_SHIFT);
+        self.data.push(0f32.to_bits());
+        self.data.extend(literals.iter().map(|l| l.0));
+        clause
+    }
+
+    fn start(clause: ClauseRef) -> usize {
+        clause.0 as usize
+    }
+
+    pub fn len(&self, clause: ClauseRef) -> usize {
+        self.data[Self::start(clause)] as usize
+    }
+
+    pub fn lit(&self, clause: ClauseRef, i: usize) -> Lit {
+        Lit(self.data[Self::start(clause) + HEADER + i])
+    }
+
+    pub fn literals(&self, clause: ClauseRef) -> impl Iterator<Item = Lit> + '_ {
+        let start = Self::start(clause) + HEADER;
+        self.data[start..start + self.len(clause)]
+            .iter()
+            .map(|&l| Lit(l))
+    }
+
+    pub fn swap(&mut self, clause: ClauseRef, i: usize, j: usize) {
+        let start = Self::start(clause) + HEADER;
+        self.data.swap(start + i, start + j);
+    }
+
+    fn flags(&self, clause: ClauseRef) -> u32 {
+        self.data[Self::start(clause) + 1]
+    }
+
+    fn set_flag(&mut self, clause: ClauseRef, flag: u32, on: bool) {
+        let flags = &mut self.data[Self::start(clause) + 1];
+        if on {
+            *flags |= flag;
+        } else {
+            *flags &= !flag;
+        }
+    }
+
+    pub fn is_learned(&self, clause: ClauseRef) -> bool {
+        self.flags(clause) & LEARNED != 0
+    }
+
+    pub fn is_deleted(&self, clause: ClauseRef) -> bool {
+        self.flags(clause) & DELETED != 0
+    }
+
+    pub fn delete(&mut self, clause: ClauseRef) {
+        self.set_flag(clause, DELETED, true);
+        self.wasted += HEADER + self.len(clause);
+    }
+
+    pub fn lbd(&self, clause: ClauseRef) -> usize {
+        (self.flags(clause) >> LBD_SHIFT) as usize
+    }
+
+    fn set_lbd(&mut self, clause: ClauseRef, lbd: usize) {
+        let flags = &mut self.data[Self::start(clause) + 1];
+        *flags = (*flags & ((1 << LBD_SHIFT) - 1)) | (lbd as u32) << LBD_SHIFT;
+    }
+
+    pub fn activity(&self, clause: ClauseRef) -> f32 {
+        f32::from_bits(self.data[Self::start(clause) + 2])
+    }
+
+    fn set_activity(&mut self, clause: ClauseRef, activity: f32) {
+        self.data[Self::start(clause) + 2] = activity.to_bits();
+    }
+
+    /// Words held by deleted clauses.
+    pub fn wasted(&self) -> usize {
+        self.wasted
+    }
+
+    /// Moves the live clauses to a fresh buffer, dropping deleted ones.
+    /// Returns the new reference of every live clause.
+    pub fn collect(&mut self) -> HashMap<ClauseRef, ClauseRef> {
+        let mut data = Vec::with_capacity(self.data.len() - self.wasted);
+        let mut remap = HashMap::new();
+        let mut start = 0;
+        while start < self.data.len() {
+            let clause = ClauseRef(start as u32);
+            let end = start + HEADER + self.len(clause);
+            if !self.is_deleted(clause) {
+                remap.insert(clause, ClauseRef(data.len() as u32));
+                data.extend_from_slice(&self.data[start..end]);
+            }
+            start = end;
+        }
+        self.data = data;
+        self.wasted = 0;
+        remap
+    }
+}
+
+/// Retention tier of a learned clause, derived from its LBD.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Tier {
+    /// Never deleted.
+    Core,
+    /// Kept while it keeps taking part in conflicts.
+    Tier2,
+    /// Half of the least active ones are deleted at every reduction.
+    Local,
+}
+
+impl Tier {
+    pub fn new(lbd: usize) -> Self {
+        match lbd {
+            0..=2 => Tier::Core,
+            3..=6 => Tier::Tier2,
+            _ => Tier::Local,
+        }
+    }
+}
+
+/// The learned clauses in the arena, with their activity increment and reduction schedule.
+pub struct LearnedClauses {
+    clauses: Vec<ClauseRef>,
+    increment: f32,
+    conflicts: u64,
+    next_reduction: u64,
+    reductions: u64,
+}
+
+impl Default for LearnedClauses {
+    fn default() -> Self {
+        Self {
+            clauses: vec![],
+            increment: 1.,
+            conflicts: 0,
+            next_reduction: FIRST_REDUCTION,
+            reductions: 0,
+        }
+    }
+}
+
+impl LearnedClauses {
+    pub fn len(&self) -> usize {
+        self.clauses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    pub fn push(&mut self, arena: &mut ClauseArena, clause: ClauseRef) {
+        arena.set_activity(clause, self.increment);
+        self.clauses.push(clause);
+    }
+
+    /// Called for every learned clause resolved during conflict analysis,
+    /// with its LBD under the current assignment.
+    pub fn bump(&mut self, arena: &mut ClauseArena, clause: ClauseRef, lbd: usize) {
+        arena.set_flag(clause, USED, true);
+        if lbd < arena.lbd(clause) {
+            arena.set_lbd(clause, lbd);
+        }
+        let activity = arena.activity(clause) + self.increment;
+        arena.set_activity(clause, activity);
+        if activity > 1e20 {
+            for &c in &self.clauses {
+                arena.set_activity(c, arena.activity(c) * 1e-20);
+            }
+            self.increment *= 1e-20;
+        }
+    }
+
+    pub fn on_conflict(&mut self) {
+        self.conflicts += 1;
+        self.increment /= ACTIVITY_DECAY;
+    }
+
+    pub fn should_reduce(&self) -> bool {
+        self.conflicts >= self.next_reduction
+    }
+
+    pub fn reductions(&self) -> u64 {
+        self.reductions
+    }
+
+    /// Marks the less useful half of the deletable clauses as deleted in the arena,
+    /// and returns them. Clauses that are `locked` (reasons of current assignments) are kept.
+    pub fn reduce(
+        &mut self,
+        arena: &mut ClauseArena,
+        locked: impl Fn(ClauseRef) -> bool,
+    ) -> Vec<ClauseRef> {
+        self.reductions += 1;
+        self.next_reduction =
+            self.conflicts + FIRST_REDUCTION + REDUCTION_INCREMENT * self.reductions;
+        let mut candidates: Vec<_> = self
+            .clauses
+            .iter()
+            .copied()
+            .filter(|&c| {
+                !locked(c)
+                    && match Tier::new(arena.lbd(c)) {
+                        Tier::Core => false,
+                        Tier::Tier2 => arena.flags(c) & USED == 0,
+                        Tier::Local => true,
+                    }
+            })
+            .collect();
+        candidates.sort_by(|&a, &b| arena.activity(a).total_cmp(&arena.activity(b)));
+        candidates.truncate(candidates.len() / 2);
+        candidates.iter().for_each(|&c| arena.delete(c));
+        self.clauses.retain(|&c| {
+            arena.set_flag(c, USED, false);
+            !arena.is_deleted(c)
+        });
+        candidates
+    }
+
+    /// Follows an arena garbage collection, forgetting the deleted clauses.
+    pub fn relocate(&mut self, remap: &HashMap<ClauseRef, ClauseRef>) {
+        self.clauses
+            .retain_mut(|c| remap.get(c).map(|&new| *c = new).is_some());
+    }
+}
@@ -1,3 +1,7 @@
+use crate::error::ErrorBehavior;
+use crate::heuristic::Heuristic;
+use crate::proof::ProofFormat;
+use crate::restart::RestartPolicy;
 use clap::Parser;
 use strum_macros::*;
 
@@ -5,14 +9,40 @@ use strum_macros::*;
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 pub struct Cli {
-    /// The solver to used
+    /// The solver to used, or CHECK to check a proof
     pub solver: Solver,
-    /// The path to the file to read
+    /// The path to the file to read, `-` to read commands from the standard input as they
+    /// come, a DIMACS CNF for CHECK
     pub path: std::path::PathBuf,
+    /// The branching heuristic of the CDCL solver: VSIDS, VMTF or LRB
+    #[clap(long, default_value = "VSIDS")]
+    pub heuristic: Heuristic,
+    /// The restart policy of the CDCL solver: NONE, LUBY, GEOMETRIC or GLUCOSE
+    #[clap(long, default_value = "LUBY")]
+    pub restart: RestartPolicy,
+    /// Write a DRAT proof of the CDCL solver to this file, or the proof to CHECK
+    #[clap(long)]
+    pub proof: Option<std::path::PathBuf>,
+    /// Check a SAT answer of the CDCL solver against the clauses and the asserted terms
+    #[clap(long)]
+    pub check_model: bool,
+    /// The proof to CHECK is in LRAT rather than DRAT
+    #[clap(long)]
+    pub lrat: bool,
+    /// The format of the DRAT proof: TEXT or BINARY
+    #[clap(long, default_value = "TEXT")]
+    pub proof_format: ProofFormat,
+    /// Write the clauses given to the CDCL solver, which the proof refers to, in DIMACS CNF
+    #[clap(long)]
+    pub cnf: Option<std::path::PathBuf>,
+    /// What to do after an error: immediate-exit or continued-execution
+    #[clap(long, default_value = "continued-execution")]
+    pub error_behavior: ErrorBehavior,
 }
 
 #[derive(EnumString, AsRefStr)]
 pub enum Solver {
     BRUTE,
     CDCL,
+    CHECK,
 }
@@ -1,11 +1,35 @@
 pub const PRINT_SUCCESS: &str = "print-success";
 pub const PRODUCE_MODELS: &str = "produce-models";
+pub const PRODUCE_UNSAT_CORES: &str = "produce-unsat-cores";
+pub const REGULAR_OUTPUT_CHANNEL: &str = "regular-output-channel";
+pub const DIAGNOSTIC_OUTPUT_CHANNEL: &str = "diagnostic-output-channel";
+pub const ERROR_BEHAVIOR: &str = "error-behavior";
+pub const NAMED: &str = "named";
 pub const AND: &str = "and";
 pub const OR: &str = "or";
 pub const NOT: &str = "not";
 pub const IMPLICATION: &str = "=>";
 pub const XOR: &str = "xor";
 pub const EQUALITY: &str = "=";
+pub const DISTINCT: &str = "distinct";
+pub const ITE: &str = "ite";
+pub const TRUE: &str = "true";
+pub const FALSE: &str = "false";
+pub const BOOL: &str = "Bool";
+/// The symbols of the Core theory, which cannot be declared or defined.
+pub const CORE_SYMBOLS: [&str; 11] = [
+    TRUE,
+    FALSE,
+    NOT,
+    AND,
+    OR,
+    XOR,
+    IMPLICATION,
+    EQUALITY,
+    DISTINCT,
+    ITE,
+    BOOL,
+];
 
 pub enum Logic {
     QfUf,
@@ -13,12 +37,11 @@ pub enum Logic {
 }
 
 impl Logic {
-    pub fn new(logic: &str) -> Self {
+    pub fn new(logic: &str) -> Option<Self> {
         match logic {
-            "QF_UF" => Self::QfUf,
-            "QF_LIA" => Self::QfLia,
-            "QF_LRA" => unimplemented!(),
-            _ => panic!("Invalid logic"),
+            "QF_UF" => Some(Self::QfUf),
+            "QF_LIA" => Some(Self::QfLia),
+            _ => None,
         }
     }
 }
@@ -1,38 +1,185 @@
-use crate::assertion_set::{and, equality, implication, or, xor, AssertionSet, Clause, Literal};
+use crate::assertion_set::{
+    and, equality, ite, or, AssertionSet, Clause, Definition, Literal, Signature,
+};
+use crate::cli::Solver;
 use crate::constants::*;
-use crate::get_id;
+use crate::error::{ErrorBehavior, RusmtError};
+use crate::heuristic::Heuristic;
+use crate::proof::{write_dimacs, Proof, ProofFormat};
+use crate::reader::CommandReader;
+use crate::restart::RestartPolicy;
+use crate::solver::{CDCLSolver, Res, SATSolver};
+use crate::variable_manager::VariableManager;
+use itertools::Itertools;
+use log::info;
 use smt2parser::concrete::{
-    AttributeValue, Command, Identifier, Keyword, QualIdentifier, Symbol, Term,
+    AttributeValue, Command, Constant, Identifier, Keyword, QualIdentifier, Sort, Symbol, Term,
 };
 use smt2parser::Numeral;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::PathBuf;
+
+/// How `check-sat` is answered and errors are handled, as selected on the command line.
+pub struct SolverOptions {
+    pub solver: Solver,
+    pub heuristic: Heuristic,
+    pub restart: RestartPolicy,
+    /// Where to write the DRAT proof of the CDCL solver, which persists across `check-sat`s.
+    pub proof: Option<(PathBuf, ProofFormat)>,
+    /// Where to write the clauses given to the solver up to the last `check-sat`.
+    pub cnf: Option<PathBuf>,
+    pub check_model: bool,
+    pub error_behavior: ErrorBehavior,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self {
+            solver: Solver::CDCL,
+            heuristic: Heuristic::VSIDS,
+            restart: RestartPolicy::LUBY,
+            proof: None,
+            cnf: None,
+            check_model: false,
+            error_behavior: ErrorBehavior::ContinuedExecution,
+        }
+    }
+}
 
 pub struct Context {
+    options: SolverOptions,
+    variables: VariableManager,
     logic: Option<Logic>,
     print_success: bool,
+    /// Where responses go, stdout unless `:regular-output-channel` is set.
+    regular_output: Box<dyn Write>,
+    /// Where `(error ...)` responses go, stdout unless `:diagnostic-output-channel` is set.
+    diagnostic_output: Box<dyn Write>,
     produce_models: bool,
+    produce_unsat_cores: bool,
     exit: bool,
     assertion_sets: Vec<AssertionSet>,
+    /// Answer of the last `check-sat`.
+    res: Option<Res>,
+    /// Names of the assertions in the core of the last UNSAT answer.
+    unsat_core: Vec<Symbol>,
+    /// Model of the last SAT answer, from ids to values.
+    model: HashMap<usize, bool>,
+    /// The CDCL solver, created at the first `check-sat` and kept until the end, so that
+    /// learned clauses carry over. Pushed levels are guarded by their activation literal.
+    solver: Option<CDCLSolver>,
+    /// Every clause given to `solver`, in order.
+    given: Vec<Clause>,
+    /// The literal standing for `true`, fixed by a unit clause at the bottom of the stack.
+    true_literal: Option<Literal>,
+    /// The literals of the symbols bound by `let` and of macro parameters while their
+    /// scope is parsed, innermost last.
+    bindings: HashMap<Symbol, Vec<Literal>>,
 }
 
 impl Default for Context {
     fn default() -> Self {
         Self {
+            options: SolverOptions::default(),
+            variables: VariableManager::default(),
             logic: None,
             print_success: false,
+            regular_output: Box::new(std::io::stdout()),
+            diagnostic_output: Box::new(std::io::stdout()),
             produce_models: false,
+            produce_unsat_cores: false,
             exit: false,
             assertion_sets: vec![AssertionSet::default()],
+            res: None,
+            unsat_core: vec![],
+            model: HashMap::new(),
+            solver: None,
+            given: vec![],
+            true_literal: None,
+            bindings: HashMap::new(),
         }
     }
 }
 
 impl Context {
+    pub fn new(options: SolverOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Executes `commands`, answering each error with an `(error ...)` response.
     pub fn process_commands(&mut self, commands: Vec<Command>) {
         for command in commands {
             if self.exit {
                 break;
             }
-            self.process_command(command);
+            if let Err(error) = self.process_command(command) {
+                self.report(&error, None);
+            }
+        }
+    }
+
+    /// Reads the commands of `reader` and executes each one as soon as it is complete.
+    /// Errors are answered with their location, `path` and a line, and a malformed command
+    /// is skipped up to its closing parenthesis.
+    pub fn run(&mut self, reader: impl BufRead, path: &str) {
+        for command in CommandReader::new(reader) {
+            let command = match command {
+                Ok(command) => command,
+                Err(error) => {
+                    let error = RusmtError::Io {
+                        path: PathBuf::from(path),
+                        error,
+                    };
+                    self.report(&error, None);
+                    break;
+                }
+            };
+            let (line, result) = match command.parse() {
+                Ok(parsed) => (command.line, self.process_command(parsed)),
+                Err((line, error)) => (line, Err(error)),
+            };
+            if let Err(error) = result {
+                self.report(&error, Some(format!("{}:{}", path, line)));
+            }
+            if self.exit {
+                break;
+            }
+        }
+    }
+
+    /// Writes `response` on the regular output channel and flushes it, so that a driver
+    /// reading over a pipe gets it before sending the next command.
+    fn respond(&mut self, response: impl Display) {
+        let written =
+            writeln!(self.regular_output, "{}", response).and_then(|_| self.regular_output.flush());
+        if written.is_err() {
+            // Nobody reads the responses anymore.
+            self.exit = true;
+        }
+    }
+
+    /// Answers `error` on the diagnostic output channel, and stops processing commands
+    /// if the error behavior is `immediate-exit`.
+    fn report(&mut self, error: &RusmtError, location: Option<String>) {
+        let message = match location {
+            Some(location) => format!("{}: {}", location, error),
+            None => error.to_string(),
+        };
+        // Quotes in SMT-LIB string literals are escaped by doubling them.
+        let response = format!("(error \"{}\")", message.replace('"', "\"\""));
+        let written = writeln!(self.diagnostic_output, "{}", response)
+            .and_then(|_| self.diagnostic_output.flush());
+        if written.is_err() {
+            println!("{}", response);
+        }
+        if self.options.error_behavior == ErrorBehavior::ImmediateExit {
+            self.exit = true;
         }
     }
 
@@ -40,13 +187,245 @@ impl Context {
         self.logic.is_none()
     }
 
-    pub fn solve(&mut self) {}
+    /// Answers `check-sat`: solves the current assertions, with the selectors of named
+    /// assertions as assumptions, and prints the result.
+    /// The CDCL solver is only given the clauses asserted since the last `check-sat`,
+    /// and assumes the activation literals of the pushed levels.
+    pub fn solve(&mut self) -> Result<(), RusmtError> {
+        self.solve_assuming(vec![])
+    }
+
+    /// Answers `check-sat-assuming`: `check-sat` with the literals of `literals` assumed,
+    /// for this call only.
+    fn check_sat_assuming(&mut self, literals: Vec<(Symbol, bool)>) -> Result<(), RusmtError> {
+        let mut assumed = vec![];
+        let mut clauses = vec![];
+        for (symbol, value) in literals {
+            let term = Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple { symbol },
+            });
+            self.check_bool(&term)?;
+            let literal = self.parse_term(term, &mut clauses);
+            assumed.push(if value { literal } else { literal.not() });
+        }
+        self.assertion_sets.last_mut().unwrap().add_clauses(clauses);
+        self.solve_assuming(assumed)
+    }
+
+    /// `solve` with the literals of `assumed` as additional assumptions.
+    fn solve_assuming(&mut self, assumed: Vec<Literal>) -> Result<(), RusmtError> {
+        let assumed: Vec<_> = self.get_selectors().into_iter().chain(assumed).collect();
+        let (res, model, failed, proof_error) = match self.options.solver {
+            Solver::BRUTE => {
+                let mut clauses = self.get_clauses();
+                self.write_cnf(&clauses)?;
+                clauses.extend(assumed.iter().map(|&l| Clause::new(vec![l])));
+                let mut sat_solver = SATSolver::new(clauses);
+                let res = sat_solver.solve();
+                let model = sat_solver
+                    .get_assignments()
+                    .into_iter()
+                    .filter_map(|(id, value)| value.map(|v| (id, v)))
+                    .collect();
+                // Without final conflict analysis, every assumption is to blame.
+                (res, model, assumed, None)
+            }
+            Solver::CDCL => {
+                // The proof file is created before any clause is taken, so that none is lost
+                // if this fails.
+                let proof = match (&self.solver, &self.options.proof) {
+                    (None, Some((path, format))) => {
+                        let file = File::create(path).map_err(|error| RusmtError::Io {
+                            path: path.clone(),
+                            error,
+                        })?;
+                        Proof::new(Box::new(BufWriter::new(file)), *format)
+                    }
+                    _ => Proof::default(),
+                };
+                let clauses: Vec<_> = self
+                    .assertion_sets
+                    .iter_mut()
+                    .flat_map(|a| a.take_new_clauses())
+                    .collect();
+                self.given.extend(clauses.iter().cloned());
+                match &mut self.solver {
+                    Some(cdcl_solver) => {
+                        clauses.into_iter().for_each(|c| cdcl_solver.add_clause(c));
+                    }
+                    None => {
+                        let mut cdcl_solver = CDCLSolver::with_proof(clauses, proof);
+                        cdcl_solver.set_heuristic(self.options.heuristic);
+                        cdcl_solver.set_restart_policy(self.options.restart);
+                        self.solver = Some(cdcl_solver);
+                    }
+                }
+                self.write_cnf(&self.given)?;
+                let cdcl_solver = self.solver.as_mut().unwrap();
+                let assumptions: Vec<_> = self
+                    .assertion_sets
+                    .iter()
+                    .filter_map(|a| a.activation())
+                    .chain(assumed)
+                    .collect();
+                let res = cdcl_solver.solve_with_assumptions(&assumptions);
+                info!(
+                    "clauses: {}; learned: {}; reductions: {}; restarts: {}; rephases: {}",
+                    cdcl_solver.original_clauses(),
+                    cdcl_solver.learned_clauses(),
+                    cdcl_solver.reductions(),
+                    cdcl_solver.restarts(),
+                    cdcl_solver.rephases()
+                );
+                let model = cdcl_solver.get_assignments().into_iter().collect();
+                (
+                    res,
+                    model,
+                    cdcl_solver.failed_assumptions(),
+                    cdcl_solver.take_proof_error(),
+                )
+            }
+            Solver::CHECK => unreachable!(),
+        };
+        self.respond(res);
+        if self.options.check_model && res == Res::SAT {
+            for violation in self.check_model(&model) {
+                self.report(&RusmtError::ModelViolation(violation), None);
+            }
+        }
+        self.unsat_core = self
+            .assertion_sets
+            .iter()
+            .flat_map(|a| a.get_named())
+            .filter(|(_, selector)| failed.contains(selector))
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.model = model;
+        self.res = Some(res);
+        match (proof_error, &self.options.proof) {
+            (Some(error), Some((path, _))) => Err(RusmtError::Io {
+                path: path.clone(),
+                error,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_cnf(&self, clauses: &[Clause]) -> Result<(), RusmtError> {
+        if let Some(path) = &self.options.cnf {
+            File::create(path)
+                .and_then(|file| {
+                    let mut output = BufWriter::new(file);
+                    write_dimacs(clauses, &mut output)?;
+                    output.flush()
+                })
+                .map_err(|error| RusmtError::Io {
+                    path: path.clone(),
+                    error,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Pops `levels` assertion sets. Their clauses are disabled in the persistent solver,
+    /// which then deletes every clause depending on them, learned ones included.
+    fn pop(&mut self, levels: usize) {
+        let len = self.assertion_sets.len() - levels;
+        for assertion_set in self.assertion_sets.drain(len..) {
+            if let (Some(cdcl_solver), Some(clause)) =
+                (&mut self.solver, assertion_set.deactivation())
+            {
+                self.given.push(clause.clone());
+                cdcl_solver.add_clause(clause);
+            }
+        }
+        if let Some(cdcl_solver) = &mut self.solver {
+            cdcl_solver.remove_satisfied();
+        }
+    }
+
+    pub fn variables(&self) -> &VariableManager {
+        &self.variables
+    }
+
+    /// The answer of the last `check-sat`, if any.
+    pub fn result(&self) -> Option<Res> {
+        self.res
+    }
+
+    /// The response to `(get-unsat-core)`: the names of the assertions in the last core.
+    fn get_unsat_core(&self) -> Result<String, RusmtError> {
+        if !self.produce_unsat_cores {
+            Err(RusmtError::UnsatCoresDisabled)
+        } else if self.res != Some(Res::UNSAT) {
+            Err(RusmtError::NotUnsat)
+        } else {
+            Ok(format!("({})", self.unsat_core.iter().join(" ")))
+        }
+    }
+
+    /// The last model, or why there is none.
+    fn last_model(&self) -> Result<&HashMap<usize, bool>, RusmtError> {
+        if !self.produce_models {
+            Err(RusmtError::ModelsDisabled)
+        } else if self.res != Some(Res::SAT) {
+            Err(RusmtError::NotSat)
+        } else {
+            Ok(&self.model)
+        }
+    }
+
+    /// The response to `(get-model)`: a definition of every declared symbol.
+    pub(crate) fn get_model(&self) -> Result<String, RusmtError> {
+        let model = self.last_model()?;
+        let definitions = self
+            .assertion_sets
+            .iter()
+            .flat_map(|a| a.get_symbol_ids())
+            .filter(|&id| {
+                let signature = self.find_signature(id).unwrap();
+                signature.parameters.is_empty() && signature.result == bool_sort()
+            })
+            .sorted()
+            .map(|id| {
+                let symbol = self.variables.name(id).unwrap();
+                let value = model.get(&id).copied().unwrap_or(false);
+                format!("  (define-fun {} () Bool {})\n", symbol, value)
+            })
+            .join("");
+        Ok(format!("(\n{})", definitions))
+    }
+
+    /// The response to `(get-value (terms))`: each term with its value under the last model.
+    pub(crate) fn get_value(&self, terms: &[Term]) -> Result<String, RusmtError> {
+        for term in terms {
+            self.check_bool(term)?;
+        }
+        let model = self.last_model()?;
+        let values = terms
+            .iter()
+            .map(|term| format!("({} {})", term, self.evaluate(term, model)))
+            .join(" ");
+        Ok(format!("({})", values))
+    }
 
     fn parse_term(&mut self, term: Term, clauses: &mut Vec<Clause>) -> Literal {
         match term {
             Term::QualIdentifier(QualIdentifier::Simple {
                 identifier: Identifier::Simple { symbol },
-            }) => Literal::new(self.get_symbol_id(symbol)),
+            }) => {
+                if let Some(&literal) = self.bindings.get(&symbol).and_then(|b| b.last()) {
+                    return literal;
+                }
+                match symbol.0.as_str() {
+                    TRUE => self.true_literal(),
+                    FALSE => self.true_literal().not(),
+                    _ => match self.find_definition(&symbol).cloned() {
+                        Some(definition) => self.expand(definition, vec![], clauses),
+                        None => Literal::new(self.find_symbol_id(&symbol).unwrap()),
+                    },
+                }
+            }
             Term::Application {
                 qual_identifier,
                 arguments,
@@ -61,172 +440,843 @@ impl Context {
                         .into_iter()
                         .map(|term| self.parse_term(term, clauses))
                         .collect();
-                    let _literal = Literal::new(get_id());
-                    assert!(args.len() == 2 || (symbol == NOT && args.len() == 1));
+                    if let Some(definition) = self.find_definition(&Symbol(symbol.clone())) {
+                        return self.expand(definition.clone(), args, clauses);
+                    }
                     match symbol.as_str() {
-                        AND => and(args, clauses),
-                        OR => or(args, clauses),
-                        NOT => return args[0].not(),
-                        IMPLICATION => implication(args, clauses),
-                        EQUALITY => equality(args, clauses),
-                        XOR => xor(args, clauses),
+                        AND => self.gate(AND, args, clauses),
+                        OR => self.gate(OR, args, clauses),
+                        NOT => args[0].not(),
+                        ITE => self.gate(ITE, args, clauses),
+                        // Right associative: `(=> a b c)` is `(=> a (=> b c))`.
+                        IMPLICATION => args
+                            .into_iter()
+                            .rev()
+                            .reduce(|b, a| self.gate(OR, vec![a.not(), b], clauses))
+                            .unwrap(),
+                        // Left associative.
+                        XOR => args
+                            .into_iter()
+                            .reduce(|a, b| self.gate(EQUALITY, vec![a.not(), b], clauses))
+                            .unwrap(),
+                        // Chainable: `(= a b c)` is `(and (= a b) (= b c))`.
+                        EQUALITY => {
+                            let pairs = args
+                                .windows(2)
+                                .map(|pair| self.gate(EQUALITY, pair.to_vec(), clauses))
+                                .collect();
+                            self.gate(AND, pairs, clauses)
+                        }
+                        // Pairwise: the conjunction of the `xor` of every pair.
+                        DISTINCT => {
+                            let pairs = (0..args.len())
+                                .flat_map(|i| (i + 1..args.len()).map(move |j| (i, j)))
+                                .map(|(i, j)| {
+                                    self.gate(EQUALITY, vec![args[i].not(), args[j]], clauses)
+                                })
+                                .collect();
+                            self.gate(AND, pairs, clauses)
+                        }
                         _ => unimplemented!(),
                     }
                 }
                 _ => unimplemented!(),
             },
+            // Names only matter for top-level assertions, see `Command::Assert`.
+            Term::Attributes { term, .. } => self.parse_term(*term, clauses),
+            Term::Let { var_bindings, term } => {
+                // Bindings are parallel: every bound term is encoded in the outer scope,
+                // once, however often the body refers to it.
+                let bound: Vec<_> = var_bindings
+                    .into_iter()
+                    .map(|(symbol, term)| (symbol, self.parse_term(term, clauses)))
+                    .collect();
+                for (symbol, literal) in &bound {
+                    self.bindings
+                        .entry(symbol.clone())
+                        .or_default()
+                        .push(*literal);
+                }
+                let literal = self.parse_term(*term, clauses);
+                for (symbol, _) in &bound {
+                    self.bindings.get_mut(symbol).unwrap().pop();
+                }
+                literal
+            }
             _ => unimplemented!(),
         }
     }
 
-    pub fn process_command(&mut self, command: Command) {
-        if self.exit {
-            return;
+    /// Hash-consed Tseitin gates: the literal of `operator` applied to `args`, shared by
+    /// every structurally equal application in the sets on the stack. Arguments of `and`,
+    /// `or` and `=` are sorted, so argument order does not matter. A new gate is kept in
+    /// the top set, whose clauses define it, and is forgotten when that set is popped.
+    fn gate(
+        &mut self,
+        operator: &'static str,
+        mut args: Vec<Literal>,
+        clauses: &mut Vec<Clause>,
+    ) -> Literal {
+        let mut negated = false;
+        match operator {
+            AND | OR => {
+                args.sort_by_key(|l| (l.id, l.value));
+                args.dedup();
+                if args.len() == 1 {
+                    return args[0];
+                }
+            }
+            EQUALITY => {
+                args.sort_by_key(|l| (l.id, l.value));
+                // Both arguments positive: `(= (not a) (not b))` is `(= a b)`,
+                // and `(= a (not b))` is `(not (= a b))`.
+                if !args[0].value {
+                    args[0] = args[0].not();
+                    args[1] = args[1].not();
+                }
+                if !args[1].value {
+                    args[1] = args[1].not();
+                    negated = true;
+                }
+            }
+            // `(ite (not c) a b)` is `(ite c b a)`.
+            ITE if !args[0].value => args = vec![args[0].not(), args[2], args[1]],
+            _ => {}
         }
-        match command {
-            Command::Assert { term } => {
-                if self.no_logic() {
-                    panic!("no logic")
+        let gate = (operator, args);
+        let literal = match self
+            .assertion_sets
+            .iter()
+            .rev()
+            .find_map(|set| set.get_gate(&gate))
+        {
+            Some(literal) => literal,
+            None => {
+                let args = gate.1.clone();
+                let literal = Literal::new(self.variables.new_auxiliary());
+                match operator {
+                    AND => and(literal, args, clauses),
+                    OR => or(literal, args, clauses),
+                    EQUALITY => equality(literal, args, clauses),
+                    _ => ite(literal, args, clauses),
+                }
+                self.assertion_sets
+                    .last_mut()
+                    .unwrap()
+                    .add_gate(gate, literal);
+                literal
+            }
+        };
+        if negated {
+            literal.not()
+        } else {
+            literal
+        }
+    }
+
+    /// Encodes an application of a `define-fun` macro: its body, with the parameters
+    /// bound to the literals of the arguments only.
+    fn expand(
+        &mut self,
+        definition: Definition,
+        args: Vec<Literal>,
+        clauses: &mut Vec<Clause>,
+    ) -> Literal {
+        if let Some(literal) = definition.literal {
+            return literal;
+        }
+        let parameters = definition
+            .parameters
+            .into_iter()
+            .zip(args)
+            .map(|((symbol, _), literal)| (symbol, vec![literal]))
+            .collect();
+        let outer = std::mem::replace(&mut self.bindings, parameters);
+        let literal = self.parse_term(definition.body, clauses);
+        self.bindings = outer;
+        literal
+    }
+
+    fn find_definition(&self, name: &Symbol) -> Option<&Definition> {
+        self.assertion_sets
+            .iter()
+            .rev()
+            .find_map(|set| set.get_definition(name))
+    }
+
+    fn define_fun(
+        &mut self,
+        name: Symbol,
+        parameters: Vec<(Symbol, Sort)>,
+        result: Sort,
+        body: Term,
+    ) -> Result<(), RusmtError> {
+        self.check_fresh(&name)?;
+        if let Some((symbol, sort)) = parameters.iter().find(|(_, sort)| *sort != bool_sort()) {
+            return Err(RusmtError::Sort(format!(
+                "unsupported parameter {} of sort {}, only Bool is supported",
+                symbol, sort
+            )));
+        }
+        let mut bindings = parameters
+            .iter()
+            .map(|(symbol, sort)| (symbol.clone(), vec![sort.clone()]))
+            .collect();
+        let sort = self
+            .sort_of(&body, &mut bindings)
+            .map_err(RusmtError::Sort)?;
+        if sort != result {
+            return Err(RusmtError::Sort(format!(
+                "the body of {} has sort {}, expected {}",
+                name, sort, result
+            )));
+        }
+        let mut definition = Definition {
+            parameters,
+            result,
+            body,
+            literal: None,
+        };
+        if definition.parameters.is_empty() && definition.result == bool_sort() {
+            let mut clauses = vec![];
+            let literal = self.parse_term(definition.body.clone(), &mut clauses);
+            self.assertion_sets.last_mut().unwrap().add_clauses(clauses);
+            definition.literal = Some(literal);
+        }
+        self.assertion_sets
+            .last_mut()
+            .unwrap()
+            .add_definition(name, definition);
+        Ok(())
+    }
+
+    fn declare_fun(
+        &mut self,
+        symbol: Symbol,
+        parameters: Vec<Sort>,
+        result: Sort,
+    ) -> Result<(), RusmtError> {
+        self.check_fresh(&symbol)?;
+        let id = self.variables.new_symbol(symbol.clone());
+        let assertion_set = self.assertion_sets.last_mut().unwrap();
+        assertion_set.set_id(symbol, id);
+        assertion_set.add_uninterpreted_function(id, parameters, result);
+        Ok(())
+    }
+
+    /// Rejects a declaration or definition of a Core symbol or of a symbol already in scope.
+    fn check_fresh(&self, symbol: &Symbol) -> Result<(), RusmtError> {
+        if CORE_SYMBOLS.contains(&symbol.0.as_str()) {
+            Err(RusmtError::Reserved(symbol.clone()))
+        } else if self.find_symbol_id(symbol).is_some() || self.find_definition(symbol).is_some() {
+            Err(RusmtError::AlreadyDeclared(symbol.clone()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn assert(&mut self, term: Term) -> Result<(), RusmtError> {
+        self.check_bool(&term)?;
+        let name = assertion_name(&term)?.filter(|_| self.produce_unsat_cores);
+        if let Some(name) = &name {
+            let named = |set: &AssertionSet| set.get_named().any(|(other, _)| other == name);
+            if self.assertion_sets.iter().any(named) {
+                return Err(RusmtError::AlreadyNamed(name.clone()));
+            }
+        }
+        self.assertion_sets
+            .last_mut()
+            .unwrap()
+            .add_assertion(term.clone());
+        let mut clauses = vec![];
+        let literal = self.parse_term(term, &mut clauses);
+        let assertion_set = self.assertion_sets.last_mut().unwrap();
+        if let Some(name) = name {
+            // A named assertion only holds while its selector is assumed.
+            let selector = Literal::new(self.variables.new_auxiliary());
+            clauses.push(Clause::new(vec![selector.not(), literal]));
+            assertion_set.add_named(name, selector);
+        } else {
+            clauses.push(Clause::new(vec![literal]));
+        }
+        assertion_set.add_clauses(clauses);
+        Ok(())
+    }
+
+    /// Checks that `term` is a well-sorted Boolean term.
+    fn check_bool(&self, term: &Term) -> Result<(), RusmtError> {
+        let sort = self
+            .sort_of(term, &mut HashMap::new())
+            .map_err(RusmtError::Sort)?;
+        if sort == bool_sort() {
+            Ok(())
+        } else {
+            Err(RusmtError::Sort(format!(
+                "the term {} has sort {}, expected Bool",
+                term, sort
+            )))
+        }
+    }
+
+    /// The sort of `term`, or why it is ill-sorted. Symbols are resolved through the `let`
+    /// bindings and macro parameters in `bindings`, then through the definitions and
+    /// declarations on the assertion set stack. Constants of other sorts are well-sorted,
+    /// but only Bool is encoded, so equalities and ites over them are rejected, as are
+    /// applications of declared functions.
+    fn sort_of(
+        &self,
+        term: &Term,
+        bindings: &mut HashMap<Symbol, Vec<Sort>>,
+    ) -> Result<Sort, String> {
+        match term {
+            Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple { symbol },
+            }) => {
+                if let Some(sort) = bindings.get(symbol).and_then(|b| b.last()) {
+                    return Ok(sort.clone());
+                }
+                match symbol.0.as_str() {
+                    TRUE | FALSE => Ok(bool_sort()),
+                    _ => self.application_sort(symbol, &[]),
+                }
+            }
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier: Identifier::Simple { symbol },
+                    },
+                arguments,
+            } => {
+                let sorts = arguments
+                    .iter()
+                    .map(|term| self.sort_of(term, bindings))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let arity = |expected: bool, arity: &str| {
+                    if expected {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "wrong number of arguments for {}: expected {}, got {}",
+                            symbol,
+                            arity,
+                            sorts.len()
+                        ))
+                    }
+                };
+                let expect_bool = |i: usize| {
+                    if sorts[i] == bool_sort() {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "argument {} of {} has sort {}, expected Bool",
+                            i + 1,
+                            symbol,
+                            sorts[i]
+                        ))
+                    }
+                };
+                match symbol.0.as_str() {
+                    NOT | AND | OR | IMPLICATION | XOR => {
+                        match symbol.0.as_str() {
+                            NOT => arity(sorts.len() == 1, "1")?,
+                            _ => arity(sorts.len() >= 2, "at least 2")?,
+                        }
+                        (0..sorts.len()).try_for_each(expect_bool)?;
+                        Ok(bool_sort())
+                    }
+                    EQUALITY | DISTINCT => {
+                        arity(sorts.len() >= 2, "at least 2")?;
+                        match sorts.iter().find(|&sort| *sort != sorts[0]) {
+                            Some(sort) => Err(format!(
+                                "the arguments of {} have different sorts {} and {}",
+                                symbol, sorts[0], sort
+                            )),
+                            None if sorts[0] != bool_sort() => Err(format!(
+                                "unsupported {} over sort {}, only Bool is supported",
+                                symbol, sorts[0]
+                            )),
+                            None => Ok(bool_sort()),
+                        }
+                    }
+                    ITE => {
+                        arity(sorts.len() == 3, "3")?;
+                        expect_bool(0)?;
+                        if sorts[1] != sorts[2] {
+                            return Err(format!(
+                                "the branches of ite have different sorts {} and {}",
+                                sorts[1], sorts[2]
+                            ));
+                        }
+                        if sorts[1] != bool_sort() {
+                            return Err(format!(
+                                "unsupported ite over sort {}, only Bool is supported",
+                                sorts[1]
+                            ));
+                        }
+                        Ok(bool_sort())
+                    }
+                    _ => self.application_sort(symbol, &sorts),
                 }
-                let mut clauses = vec![];
-                let literal = self.parse_term(term, &mut clauses);
-                clauses.push(Clause::new(vec![literal]));
-                self.assertion_sets.last_mut().unwrap().add_clauses(clauses);
             }
-            Command::CheckSat => {
-                self.solve();
+            Term::Attributes { term, .. } => self.sort_of(term, bindings),
+            Term::Let { var_bindings, term } => {
+                let bound = var_bindings
+                    .iter()
+                    .map(|(symbol, term)| Ok((symbol, self.sort_of(term, bindings)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                for (symbol, sort) in &bound {
+                    bindings
+                        .entry((*symbol).clone())
+                        .or_default()
+                        .push(sort.clone());
+                }
+                let sort = self.sort_of(term, bindings);
+                for (symbol, _) in bound {
+                    bindings.get_mut(symbol).unwrap().pop();
+                }
+                sort
             }
-            Command::CheckSatAssuming { .. } => {}
-            Command::DeclareConst { .. } => {}
+            _ => Err(format!("unsupported term {}", term)),
+        }
+    }
+
+    /// The sort of the defined or declared `symbol` applied to arguments of `sorts`,
+    /// a constant if there are none.
+    fn application_sort(&self, symbol: &Symbol, sorts: &[Sort]) -> Result<Sort, String> {
+        let (parameters, result, declared) = match self.find_definition(symbol) {
+            Some(definition) => (
+                definition
+                    .parameters
+                    .iter()
+                    .map(|(_, sort)| sort)
+                    .collect::<Vec<_>>(),
+                &definition.result,
+                false,
+            ),
+            None => match self.find_symbol_id(symbol) {
+                Some(id) => {
+                    let signature = self.find_signature(id).unwrap();
+                    (
+                        signature.parameters.iter().collect(),
+                        &signature.result,
+                        true,
+                    )
+                }
+                None if sorts.is_empty() => return Err(format!("unknown constant {}", symbol)),
+                None => return Err(format!("unknown function {}", symbol)),
+            },
+        };
+        if parameters.len() != sorts.len() {
+            return Err(format!(
+                "wrong number of arguments for {}: expected {}, got {}",
+                symbol,
+                parameters.len(),
+                sorts.len()
+            ));
+        }
+        if let Some(i) = (0..sorts.len()).find(|&i| *parameters[i] != sorts[i]) {
+            return Err(format!(
+                "argument {} of {} has sort {}, expected {}",
+                i + 1,
+                symbol,
+                sorts[i],
+                parameters[i]
+            ));
+        }
+        if declared && !sorts.is_empty() {
+            return Err(format!("unsupported uninterpreted function {}", symbol));
+        }
+        Ok(result.clone())
+    }
+
+    /// Executes `command`. Its response, if any, is written on the regular output channel,
+    /// `success` for commands without one if `:print-success` is set.
+    pub fn process_command(&mut self, command: Command) -> Result<(), RusmtError> {
+        if self.exit {
+            return Ok(());
+        }
+        if self.no_logic() && needs_logic(&command) {
+            return Err(RusmtError::NoLogic);
+        }
+        let responds = has_response(&command);
+        match command {
+            Command::Assert { term } => self.assert(term)?,
+            Command::CheckSat => self.solve()?,
+            Command::CheckSatAssuming { literals } => self.check_sat_assuming(literals)?,
+            Command::DeclareConst { symbol, sort } => self.declare_fun(symbol, vec![], sort)?,
             Command::DeclareDatatype { .. } => {}
             Command::DeclareDatatypes { .. } => {}
             Command::DeclareFun {
                 symbol,
                 parameters,
                 sort,
-            } => {
-                let id = self.get_symbol_id(symbol);
-                self.assertion_sets
-                    .last_mut()
-                    .unwrap()
-                    .add_uninterpreted_function(id, parameters, sort);
-            }
+            } => self.declare_fun(symbol, parameters, sort)?,
             Command::DeclareSort {
                 symbol: _,
                 arity: _,
             } => {}
-            Command::DefineFun { sig: _, term: _ } => {}
+            Command::DefineFun { sig, term } => {
+                self.define_fun(sig.name, sig.parameters, sig.result, term)?
+            }
             Command::DefineFunRec { .. } => {}
             Command::DefineFunsRec { .. } => {}
             Command::DefineSort { .. } => {}
-            Command::Echo { message } => {
-                println!("{}", message)
-            }
+            Command::Echo { message } => self.respond(message),
             Command::Exit => self.exit = true,
-            Command::GetAssertions => {}
-            Command::GetAssignment => {}
-            Command::GetInfo { .. } => {}
-            Command::GetModel => {}
-            Command::GetOption { .. } => {}
-            Command::GetProof => {}
-            Command::GetUnsatAssumptions => {}
-            Command::GetUnsatCore => {}
-            Command::GetValue { .. } => {}
+            Command::GetAssertions => self.respond("unsupported"),
+            Command::GetAssignment => self.respond("unsupported"),
+            Command::GetInfo { flag } => self.get_info(flag),
+            Command::GetModel => {
+                let model = self.get_model()?;
+                self.respond(model)
+            }
+            Command::GetOption { .. } => self.respond("unsupported"),
+            Command::GetProof => self.respond("unsupported"),
+            Command::GetUnsatAssumptions => self.respond("unsupported"),
+            Command::GetUnsatCore => {
+                let core = self.get_unsat_core()?;
+                self.respond(core)
+            }
+            Command::GetValue { terms } => {
+                let values = self.get_value(&terms)?;
+                self.respond(values)
+            }
             Command::Pop { level } => {
-                if self.no_logic() {
-                    panic!("Pop before set logic")
+                let levels = self::level(&level)?;
+                let pushed = self.assertion_sets.len() - 1;
+                if levels > pushed {
+                    return Err(RusmtError::PopTooDeep { levels, pushed });
                 }
-                if numeral_larger_than_usize(&level) {
-                    panic!("Pop level too large")
-                }
-                let level = level.to_u64_digits()[0] as usize;
-                let current_len = self.assertion_sets.len();
-                if level + 1 > current_len {
-                    panic!("Pop bottom level")
-                }
-                self.assertion_sets.truncate(current_len - level);
+                self.pop(levels);
             }
             Command::Push { level } => {
-                if self.no_logic() {
-                    panic!("Push before set logic")
-                }
-                if numeral_larger_than_usize(&level) {
-                    panic!("Push level too large")
+                for _ in 0..self::level(&level)? {
+                    let activation = Literal::new(self.variables.new_auxiliary());
+                    self.assertion_sets
+                        .push(AssertionSet::with_activation(activation));
                 }
-                self.assertion_sets
-                    .extend((0..level.to_u64_digits()[0]).map(|_| AssertionSet::default()));
             }
             Command::Reset => {}
             Command::ResetAssertions => {}
             Command::SetInfo { .. } => {}
             Command::SetLogic {
                 symbol: Symbol(symbol),
-            } => self.set_logic(symbol),
-            Command::SetOption { keyword, value } => self.set_option(keyword, value),
+            } => self.set_logic(symbol)?,
+            Command::SetOption { keyword, value } => self.set_option(keyword, value)?,
+        }
+        if self.print_success && !responds {
+            self.respond("success");
         }
+        Ok(())
     }
 
-    fn set_logic(&mut self, symbol: String) {
+    fn set_logic(&mut self, symbol: String) -> Result<(), RusmtError> {
         if !self.no_logic() {
-            panic!("Set logic twice")
+            return Err(RusmtError::LogicAlreadySet);
         }
-        self.logic = Some(Logic::new(&symbol));
+        self.logic = Some(Logic::new(&symbol).ok_or(RusmtError::UnsupportedLogic(symbol))?);
+        Ok(())
     }
 
-    fn get_symbol_id(&mut self, symbol: Symbol) -> usize {
-        for set in self.assertion_sets.iter().rev() {
-            if let Some(id) = set.get_id(&symbol) {
-                return id;
-            }
+    fn get_info(&mut self, flag: Keyword) {
+        match flag.0.as_str() {
+            ERROR_BEHAVIOR => self.respond(format!(
+                "(:{} {})",
+                ERROR_BEHAVIOR,
+                self.options.error_behavior.as_ref()
+            )),
+            _ => self.respond("unsupported"),
         }
-        let id = get_id();
-        self.assertion_sets.last_mut().unwrap().set_id(symbol, id);
-        id
     }
 
-    fn set_option(&mut self, keyword: Keyword, value: AttributeValue) {
+    fn true_literal(&mut self) -> Literal {
+        if let Some(literal) = self.true_literal {
+            return literal;
+        }
+        let literal = Literal::new(self.variables.new_auxiliary());
+        self.assertion_sets[0].add_clauses(vec![Clause::new(vec![literal])]);
+        self.true_literal = Some(literal);
+        literal
+    }
+
+    fn find_symbol_id(&self, symbol: &Symbol) -> Option<usize> {
+        self.assertion_sets
+            .iter()
+            .rev()
+            .find_map(|set| set.get_id(symbol))
+    }
+
+    fn find_signature(&self, id: usize) -> Option<&Signature> {
+        self.assertion_sets
+            .iter()
+            .rev()
+            .find_map(|set| set.get_signature(id))
+    }
+
+    fn set_option(&mut self, keyword: Keyword, value: AttributeValue) -> Result<(), RusmtError> {
         let Keyword(keyword) = keyword;
-        // boolean
+        let invalid = || RusmtError::InvalidOptionValue {
+            option: keyword.clone(),
+            value: attribute_value_to_string(&value),
+        };
         match keyword.as_str() {
-            PRINT_SUCCESS | PRODUCE_MODELS => {
-                let _boolean = match value {
-                    AttributeValue::Symbol(Symbol(sym)) => str_to_bool(&sym),
-                    _ => {
-                        unimplemented!()
-                    }
+            // Assertions made before would not be named.
+            PRODUCE_UNSAT_CORES if !self.no_logic() => {
+                return Err(RusmtError::OptionAfterLogic(keyword.clone()))
+            }
+            PRINT_SUCCESS | PRODUCE_MODELS | PRODUCE_UNSAT_CORES => {
+                let boolean = match &value {
+                    AttributeValue::Symbol(Symbol(sym)) => str_to_bool(sym),
+                    _ => None,
+                }
+                .ok_or_else(invalid)?;
+                match keyword.as_str() {
+                    PRINT_SUCCESS => self.print_success = boolean,
+                    PRODUCE_MODELS => self.produce_models = boolean,
+                    _ => self.produce_unsat_cores = boolean,
+                }
+            }
+            REGULAR_OUTPUT_CHANNEL | DIAGNOSTIC_OUTPUT_CHANNEL => {
+                let AttributeValue::Constant(Constant::String(channel)) = &value else {
+                    return Err(invalid());
                 };
+                let channel = output_channel(channel)?;
                 match keyword.as_str() {
-                    PRINT_SUCCESS => self.print_success = true,
-                    PRODUCE_MODELS => self.produce_models = true,
-                    _ => {
-                        unimplemented!()
-                    }
+                    REGULAR_OUTPUT_CHANNEL => self.regular_output = channel,
+                    _ => self.diagnostic_output = channel,
                 }
             }
-            _ => {
-                unimplemented!()
+            _ => return Err(RusmtError::UnsupportedOption(keyword.clone())),
+        }
+        Ok(())
+    }
+
+    /// The selectors of the named assertions, to be assumed when solving `get_clauses`.
+    pub fn get_selectors(&self) -> Vec<Literal> {
+        self.assertion_sets
+            .iter()
+            .flat_map(|a| a.get_named())
+            .map(|(_, selector)| *selector)
+            .collect()
+    }
+
+    /// Checks `model`, from ids to values, against every clause and every asserted term.
+    /// Variables missing from `model` are false. Returns a description of each violation.
+    pub fn check_model(&self, model: &HashMap<usize, bool>) -> Vec<String> {
+        let value = |l: &Literal| model.get(&l.id).copied().unwrap_or(false) == l.value;
+        let clauses = self
+            .get_clauses()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, c)| !c.literals.iter().any(value))
+            .map(|(i, c)| format!("clause {} {}", i, c));
+        let assertions = self
+            .assertion_sets
+            .iter()
+            .flat_map(|a| a.get_assertions())
+            .filter(|term| !self.evaluate(term, model))
+            .map(|term| format!("assertion {}", term));
+        clauses.chain(assertions).collect()
+    }
+
+    /// Evaluates a Boolean term under `model`, with the same operators as `parse_term`.
+    fn evaluate(&self, term: &Term, model: &HashMap<usize, bool>) -> bool {
+        self.evaluate_in(term, model, &mut HashMap::new())
+    }
+
+    /// `evaluate` with the values of the symbols in scope from `let` and macro parameters.
+    fn evaluate_in(
+        &self,
+        term: &Term,
+        model: &HashMap<usize, bool>,
+        bindings: &mut HashMap<Symbol, Vec<bool>>,
+    ) -> bool {
+        match term {
+            Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple { symbol },
+            }) => {
+                if let Some(&value) = bindings.get(symbol).and_then(|b| b.last()) {
+                    return value;
+                }
+                match symbol.0.as_str() {
+                    TRUE => true,
+                    FALSE => false,
+                    _ => match self.find_definition(symbol) {
+                        Some(definition) => self.evaluate(&definition.body, model),
+                        None => self
+                            .find_symbol_id(symbol)
+                            .and_then(|id| model.get(&id).copied())
+                            .unwrap_or(false),
+                    },
+                }
             }
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier:
+                            Identifier::Simple {
+                                symbol: Symbol(symbol),
+                            },
+                    },
+                arguments,
+            } => {
+                let args: Vec<bool> = arguments
+                    .iter()
+                    .map(|term| self.evaluate_in(term, model, bindings))
+                    .collect();
+                if let Some(definition) = self.find_definition(&Symbol(symbol.clone())) {
+                    let mut parameters = definition
+                        .parameters
+                        .iter()
+                        .zip(args)
+                        .map(|((symbol, _), value)| (symbol.clone(), vec![value]))
+                        .collect();
+                    return self.evaluate_in(&definition.body, model, &mut parameters);
+                }
+                match symbol.as_str() {
+                    AND => args.iter().all(|&a| a),
+                    OR => args.iter().any(|&a| a),
+                    NOT => !args[0],
+                    ITE => args[if args[0] { 1 } else { 2 }],
+                    IMPLICATION => args.iter().rev().copied().reduce(|b, a| !a || b).unwrap(),
+                    XOR => args.iter().fold(false, |a, &b| a != b),
+                    EQUALITY => args.windows(2).all(|pair| pair[0] == pair[1]),
+                    DISTINCT => args.iter().all_unique(),
+                    _ => unimplemented!(),
+                }
+            }
+            Term::Attributes { term, .. } => self.evaluate_in(term, model, bindings),
+            Term::Let { var_bindings, term } => {
+                let bound: Vec<_> = var_bindings
+                    .iter()
+                    .map(|(symbol, term)| (symbol, self.evaluate_in(term, model, bindings)))
+                    .collect();
+                for &(symbol, value) in &bound {
+                    bindings.entry(symbol.clone()).or_default().push(value);
+                }
+                let value = self.evaluate_in(term, model, bindings);
+                for (symbol, _) in bound {
+                    bindings.get_mut(symbol).unwrap().pop();
+                }
+                value
+            }
+            _ => unimplemented!(),
         }
     }
 
     pub fn get_clauses(&self) -> Vec<Clause> {
         self.assertion_sets
             .iter()
-            .map(|a| a.get_clauses())
-            .flatten()
-            .map(Clone::clone)
+            .flat_map(|a| a.get_clauses())
+            .cloned()
             .collect()
     }
 }
 
-fn str_to_bool(sym: &str) -> bool {
+/// The `:named` attribute of a top-level `(! term ...)`.
+fn assertion_name(term: &Term) -> Result<Option<Symbol>, RusmtError> {
+    let Term::Attributes { attributes, .. } = term else {
+        return Ok(None);
+    };
+    let named = attributes
+        .iter()
+        .find(|(Keyword(keyword), _)| keyword == NAMED);
+    match named {
+        Some((_, AttributeValue::Symbol(name))) => Ok(Some(name.clone())),
+        Some(_) => Err(RusmtError::InvalidName),
+        None => Ok(None),
+    }
+}
+
+fn bool_sort() -> Sort {
+    Sort::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(BOOL.to_string()),
+        },
+    }
+}
+
+/// Opens the output channel named by `:regular-output-channel` or
+/// `:diagnostic-output-channel`: `stdout`, `stderr` or a file to append to.
+fn output_channel(channel: &str) -> Result<Box<dyn Write>, RusmtError> {
+    match channel {
+        "stdout" => Ok(Box::new(std::io::stdout())),
+        "stderr" => Ok(Box::new(std::io::stderr())),
+        path => match File::options().create(true).append(true).open(path) {
+            Ok(file) => Ok(Box::new(file)),
+            Err(error) => Err(RusmtError::Io {
+                path: PathBuf::from(path),
+                error,
+            }),
+        },
+    }
+}
+
+/// Whether `command` can only come after `set-logic`.
+fn needs_logic(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Assert { .. }
+            | Command::CheckSat
+            | Command::CheckSatAssuming { .. }
+            | Command::DeclareConst { .. }
+            | Command::DeclareDatatype { .. }
+            | Command::DeclareDatatypes { .. }
+            | Command::DeclareFun { .. }
+            | Command::DeclareSort { .. }
+            | Command::DefineFun { .. }
+            | Command::DefineFunRec { .. }
+            | Command::DefineFunsRec { .. }
+            | Command::DefineSort { .. }
+            | Command::GetAssertions
+            | Command::GetAssignment
+            | Command::GetModel
+            | Command::GetProof
+            | Command::GetUnsatAssumptions
+            | Command::GetUnsatCore
+            | Command::GetValue { .. }
+            | Command::Pop { .. }
+            | Command::Push { .. }
+    )
+}
+
+/// Whether `command` has a response of its own rather than `success`.
+fn has_response(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::CheckSat
+            | Command::CheckSatAssuming { .. }
+            | Command::Echo { .. }
+            | Command::GetAssertions
+            | Command::GetAssignment
+            | Command::GetInfo { .. }
+            | Command::GetModel
+            | Command::GetOption { .. }
+            | Command::GetProof
+            | Command::GetUnsatAssumptions
+            | Command::GetUnsatCore
+            | Command::GetValue { .. }
+    )
+}
+
+fn str_to_bool(sym: &str) -> Option<bool> {
     match sym {
-        "false" => false,
-        "true" => true,
-        _ => unimplemented!(),
+        "false" => Some(false),
+        "true" => Some(true),
+        _ => None,
+    }
+}
+
+fn attribute_value_to_string(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::None => String::new(),
+        AttributeValue::Constant(constant) => constant.to_string(),
+        AttributeValue::Symbol(symbol) => symbol.to_string(),
+        AttributeValue::SExpr(exprs) => format!("({})", exprs.iter().join(" ")),
     }
 }
 
-fn numeral_larger_than_usize(num: &Numeral) -> bool {
-    num.bits() > std::mem::size_of::<usize>() as u64
+/// The number of levels of a `push` or `pop`.
+fn level(level: &Numeral) -> Result<usize, RusmtError> {
+    usize::try_from(level).map_err(|_| RusmtError::LevelTooLarge)
 }
@@ -0,0 +1,98 @@
+use smt2parser::concrete::Symbol;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use strum_macros::{AsRefStr, EnumString};
+
+/// An error in a command, answered with `(error "...")`.
+#[derive(Debug)]
+pub enum RusmtError {
+    /// The input is not a well-formed command.
+    Parse(String),
+    /// A command that needs a logic came before `set-logic`.
+    NoLogic,
+    LogicAlreadySet,
+    UnsupportedLogic(String),
+    /// A `push` or `pop` level that does not fit in a `usize`.
+    LevelTooLarge,
+    /// A `pop` of more levels than were pushed.
+    PopTooDeep {
+        levels: usize,
+        pushed: usize,
+    },
+    UnsupportedOption(String),
+    /// An option that can only be set before `set-logic`.
+    OptionAfterLogic(String),
+    InvalidOptionValue {
+        option: String,
+        value: String,
+    },
+    AlreadyDeclared(Symbol),
+    /// A `:named` attribute naming two assertions on the stack.
+    AlreadyNamed(Symbol),
+    /// A `:named` attribute without a symbol.
+    InvalidName,
+    /// A declaration or definition of a symbol of the Core theory.
+    Reserved(Symbol),
+    /// An undeclared symbol or an ill-sorted term.
+    Sort(String),
+    ModelsDisabled,
+    UnsatCoresDisabled,
+    /// `get-model` or `get-value` when the last `check-sat` was not sat.
+    NotSat,
+    /// `get-unsat-core` when the last `check-sat` was not unsat.
+    NotUnsat,
+    /// `--check-model` found a clause or an assertion violated by a SAT model.
+    ModelViolation(String),
+    /// Writing the proof, the CNF or diagnostics to `path` failed.
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+}
+
+impl Display for RusmtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(message) | Self::Sort(message) => write!(f, "{}", message),
+            Self::NoLogic => write!(f, "no logic is set"),
+            Self::LogicAlreadySet => write!(f, "the logic is already set"),
+            Self::UnsupportedLogic(logic) => write!(f, "unsupported logic {}", logic),
+            Self::LevelTooLarge => write!(f, "level too large"),
+            Self::PopTooDeep { levels, pushed } => {
+                write!(f, "cannot pop {} levels, only {} pushed", levels, pushed)
+            }
+            Self::UnsupportedOption(option) => write!(f, "unsupported option :{}", option),
+            Self::OptionAfterLogic(option) => {
+                write!(f, "option :{} can only be set before set-logic", option)
+            }
+            Self::InvalidOptionValue { option, value } => {
+                write!(f, "invalid value {} for option :{}", value, option)
+            }
+            Self::AlreadyDeclared(symbol) => write!(f, "symbol {} already declared", symbol),
+            Self::AlreadyNamed(name) => write!(f, "name {} already names an assertion", name),
+            Self::InvalidName => write!(f, "the :named attribute needs a symbol"),
+            Self::Reserved(symbol) => write!(f, "symbol {} is reserved by the Core theory", symbol),
+            Self::ModelsDisabled => write!(f, "model production is not enabled"),
+            Self::UnsatCoresDisabled => write!(f, "unsat core production is not enabled"),
+            Self::NotSat => write!(f, "the last check-sat was not sat"),
+            Self::NotUnsat => write!(f, "the last check-sat was not unsat"),
+            Self::ModelViolation(violation) => {
+                write!(f, "internal error: the model violates {}", violation)
+            }
+            Self::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for RusmtError {}
+
+/// What to do after an error, as answered to `(get-info :error-behavior)`.
+#[derive(EnumString, AsRefStr, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorBehavior {
+    /// Stop processing commands.
+    #[strum(serialize = "immediate-exit")]
+    ImmediateExit,
+    /// Ignore the failed command and go on with the next one.
+    #[strum(serialize = "continued-execution")]
+    ContinuedExecution,
+}
@@ -0,0 +1,342 @@
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(EnumString, AsRefStr, Copy, Clone)]
+pub enum Heuristic {
+    VSIDS,
+    VMTF,
+    LRB,
+}
+
+impl Heuristic {
+    pub fn build(self, variables: usize) -> Box<dyn BranchingHeuristic> {
+        match self {
+            Heuristic::VSIDS => Box::new(Vsids::new(variables)),
+            Heuristic::VMTF => Box::new(Vmtf::new(variables)),
+            Heuristic::LRB => Box::new(Lrb::new(variables)),
+        }
+    }
+}
+
+/// Picks the next decision variable. The solver reports every assignment,
+/// every undone assignment and every analyzed conflict.
+pub trait BranchingHeuristic {
+    /// Registers a new variable, numbered after the existing ones.
+    fn add_variable(&mut self);
+
+    fn on_assign(&mut self, _variable: usize) {}
+
+    fn on_unassign(&mut self, variable: usize);
+
+    /// `involved` holds the variables seen during conflict analysis.
+    fn on_conflict(&mut self, involved: &[usize]);
+
+    fn next_variable(&mut self, assigned: &dyn Fn(usize) -> bool) -> Option<usize>;
+}
+
+/// Binary max-heap of variables ordered by an external score slice,
+/// with a position index so scores can change while a variable is queued.
+pub struct VarHeap {
+    heap: Vec<usize>,
+    positions: Vec<Option<usize>>,
+}
+
+impl VarHeap {
+    pub fn new(variables: usize) -> Self {
+        Self {
+            heap: (0..variables).collect(),
+            positions: (0..variables).map(Some).collect(),
+        }
+    }
+
+    /// Queues a new variable, numbered after the existing ones.
+    pub fn add_variable(&mut self, scores: &[f64]) {
+        let variable = self.positions.len();
+        self.positions.push(None);
+        self.insert(variable, scores);
+    }
+
+    pub fn contains(&self, variable: usize) -> bool {
+        self.positions[variable].is_some()
+    }
+
+    pub fn insert(&mut self, variable: usize, scores: &[f64]) {
+        if self.contains(variable) {
+            return;
+        }
+        self.positions[variable] = Some(self.heap.len());
+        self.heap.push(variable);
+        self.sift_up(self.heap.len() - 1, scores);
+    }
+
+    pub fn pop(&mut self, scores: &[f64]) -> Option<usize> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let top = self.heap.swap_remove(0);
+        self.positions[top] = None;
+        if let Some(&moved) = self.heap.first() {
+            self.positions[moved] = Some(0);
+            self.sift_down(0, scores);
+        }
+        Some(top)
+    }
+
+    /// Restores the heap order after the score of `variable` changed.
+    pub fn update(&mut self, variable: usize, scores: &[f64]) {
+        if let Some(position) = self.positions[variable] {
+            let position = self.sift_up(position, scores);
+            self.sift_down(position, scores);
+        }
+    }
+
+    fn before(a: usize, b: usize, scores: &[f64]) -> bool {
+        scores[a] > scores[b] || (scores[a] == scores[b] && a < b)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.positions[self.heap[i]] = Some(i);
+        self.positions[self.heap[j]] = Some(j);
+    }
+
+    fn sift_up(&mut self, mut position: usize, scores: &[f64]) -> usize {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if !Self::before(self.heap[position], self.heap[parent], scores) {
+                break;
+            }
+            self.swap(position, parent);
+            position = parent;
+        }
+        position
+    }
+
+    fn sift_down(&mut self, mut position: usize, scores: &[f64]) {
+        loop {
+            let mut best = position;
+            for child in [2 * position + 1, 2 * position + 2] {
+                if child < self.heap.len()
+                    && Self::before(self.heap[child], self.heap[best], scores)
+                {
+                    best = child;
+                }
+            }
+            if best == position {
+                break;
+            }
+            self.swap(position, best);
+            position = best;
+        }
+    }
+}
+
+/// Exponential VSIDS: every analyzed variable is bumped by an increment that
+/// grows geometrically, which is equivalent to decaying all activities.
+pub struct Vsids {
+    activity: Vec<f64>,
+    increment: f64,
+    decay: f64,
+    heap: VarHeap,
+}
+
+impl Vsids {
+    pub fn new(variables: usize) -> Self {
+        Self {
+            activity: vec![0.; variables],
+            increment: 1.,
+            decay: 0.95,
+            heap: VarHeap::new(variables),
+        }
+    }
+
+    fn bump(&mut self, variable: usize) {
+        self.activity[variable] += self.increment;
+        if self.activity[variable] > 1e100 {
+            self.activity.iter_mut().for_each(|a| *a *= 1e-100);
+            self.increment *= 1e-100;
+        }
+        self.heap.update(variable, &self.activity);
+    }
+}
+
+impl BranchingHeuristic for Vsids {
+    fn add_variable(&mut self) {
+        self.activity.push(0.);
+        self.heap.add_variable(&self.activity);
+    }
+
+    fn on_unassign(&mut self, variable: usize) {
+        self.heap.insert(variable, &self.activity);
+    }
+
+    fn on_conflict(&mut self, involved: &[usize]) {
+        involved.iter().for_each(|&v| self.bump(v));
+        self.increment /= self.decay;
+    }
+
+    fn next_variable(&mut self, assigned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        while let Some(variable) = self.heap.pop(&self.activity) {
+            if !assigned(variable) {
+                return Some(variable);
+            }
+        }
+        None
+    }
+}
+
+/// Variable move-to-front: bumped variables move to the front of a doubly linked
+/// queue, and decisions search backwards from a cached position.
+pub struct Vmtf {
+    prev: Vec<Option<usize>>,
+    next: Vec<Option<usize>>,
+    stamp: Vec<u64>,
+    last: Option<usize>,
+    first: Option<usize>,
+    search: Option<usize>,
+    timestamp: u64,
+}
+
+impl Vmtf {
+    pub fn new(variables: usize) -> Self {
+        Self {
+            prev: (0..variables).map(|v| v.checked_sub(1)).collect(),
+            next: (0..variables)
+                .map(|v| Some(v + 1).filter(|&n| n < variables))
+                .collect(),
+            stamp: (0..variables as u64).collect(),
+            first: (variables > 0).then_some(0),
+            last: variables.checked_sub(1),
+            search: variables.checked_sub(1),
+            timestamp: variables as u64,
+        }
+    }
+
+    fn move_to_front(&mut self, variable: usize) {
+        if self.last == Some(variable) {
+            return;
+        }
+        let (prev, next) = (self.prev[variable], self.next[variable]);
+        match prev {
+            Some(p) => self.next[p] = next,
+            None => self.first = next,
+        }
+        if let Some(n) = next {
+            self.prev[n] = prev;
+        }
+        if let Some(last) = self.last {
+            self.next[last] = Some(variable);
+        }
+        self.prev[variable] = self.last;
+        self.next[variable] = None;
+        self.last = Some(variable);
+        self.timestamp += 1;
+        self.stamp[variable] = self.timestamp;
+    }
+}
+
+impl BranchingHeuristic for Vmtf {
+    fn add_variable(&mut self) {
+        let variable = self.stamp.len();
+        self.prev.push(self.last);
+        self.next.push(None);
+        match self.last {
+            Some(last) => self.next[last] = Some(variable),
+            None => self.first = Some(variable),
+        }
+        self.last = Some(variable);
+        self.stamp.push(self.timestamp);
+        self.timestamp += 1;
+        self.search = Some(variable);
+    }
+
+    fn on_unassign(&mut self, variable: usize) {
+        if self
+            .search
+            .is_none_or(|s| self.stamp[variable] > self.stamp[s])
+        {
+            self.search = Some(variable);
+        }
+    }
+
+    fn on_conflict(&mut self, involved: &[usize]) {
+        // Bumped variables are still assigned; backtracking moves the search back to them.
+        let mut bumped = involved.to_vec();
+        bumped.sort_by_key(|&v| self.stamp[v]);
+        bumped.into_iter().for_each(|v| self.move_to_front(v));
+    }
+
+    fn next_variable(&mut self, assigned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        let mut cur = self.search;
+        while let Some(variable) = cur {
+            if !assigned(variable) {
+                self.search = Some(variable);
+                return Some(variable);
+            }
+            cur = self.prev[variable];
+        }
+        None
+    }
+}
+
+/// Learning-rate branching: a variable's score is an exponential moving average of
+/// the fraction of conflicts it took part in while it was assigned.
+pub struct Lrb {
+    q: Vec<f64>,
+    assigned_at: Vec<u64>,
+    participated: Vec<u64>,
+    conflicts: u64,
+    alpha: f64,
+    heap: VarHeap,
+}
+
+impl Lrb {
+    pub fn new(variables: usize) -> Self {
+        Self {
+            q: vec![0.; variables],
+            assigned_at: vec![0; variables],
+            participated: vec![0; variables],
+            conflicts: 0,
+            alpha: 0.4,
+            heap: VarHeap::new(variables),
+        }
+    }
+}
+
+impl BranchingHeuristic for Lrb {
+    fn add_variable(&mut self) {
+        self.q.push(0.);
+        self.assigned_at.push(0);
+        self.participated.push(0);
+        self.heap.add_variable(&self.q);
+    }
+
+    fn on_assign(&mut self, variable: usize) {
+        self.assigned_at[variable] = self.conflicts;
+        self.participated[variable] = 0;
+    }
+
+    fn on_unassign(&mut self, variable: usize) {
+        let interval = self.conflicts - self.assigned_at[variable];
+        if interval > 0 {
+            let reward = self.participated[variable] as f64 / interval as f64;
+            self.q[variable] = (1. - self.alpha) * self.q[variable] + self.alpha * reward;
+            self.heap.update(variable, &self.q);
+        }
+        self.heap.insert(variable, &self.q);
+    }
+
+    fn on_conflict(&mut self, involved: &[usize]) {
+        self.conflicts += 1;
+        involved.iter().for_each(|&v| self.participated[v] += 1);
+        self.alpha = (self.alpha - 1e-6).max(0.06);
+    }
+
+    fn next_variable(&mut self, assigned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        while let Some(variable) = self.heap.pop(&self.q) {
+            if !assigned(variable) {
+                return Some(variable);
+            }
+        }
+        None
+    }
+}
@@ -1,43 +1,45 @@
-use log::info;
+use rusmt::checker::{check_drat, check_lrat, parse_dimacs};
 use rusmt::cli::{Cli, Solver};
-use smt2parser::{concrete, CommandStream};
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
 
 use clap::Parser;
-use rusmt::context::Context;
-use rusmt::solver::{CDCLSolver, SATSolver};
+use rusmt::context::{Context, SolverOptions};
 
 fn main() {
     pretty_env_logger::init();
     let args = Cli::parse();
-    let input = fs::read(args.path).unwrap();
-    let stream = CommandStream::new(
-        &input[..],
-        concrete::SyntaxBuilder,
-        Some("optional/path/to/file".to_string()),
-    );
-    let mut context = Context::default();
-    let commands = stream.collect::<Result<Vec<_>, _>>().unwrap();
-    context.process_commands(commands);
-    println!("Using solver: {}", args.solver.as_ref());
-    match args.solver {
-        Solver::BRUTE => {
-            let mut sat_solver = SATSolver::new(context.get_clauses());
-            println!("res: {}", sat_solver.solve());
-            // info!("assignment = {:?};", sat_solver.get_assignments()
-            // );
-        }
-        Solver::CDCL => {
-            let mut cdcl_solver = CDCLSolver::new(context.get_clauses());
-
-            println!("{}", cdcl_solver.solve());
-            // println!("ids: {}; freq: {}", ids.len(), frequency.len());
-            println!("End clauses length: {}", cdcl_solver.clauses.len());
-            // info!("assignment = {:?}",
-            //         cdcl_solver.get_assignments()
-            // );
+    if let Solver::CHECK = args.solver {
+        match fs::read(&args.path) {
+            Ok(input) => check_proof(&args, &input),
+            Err(error) => unreadable(&args.path, error),
         }
+        return;
     }
+    // Commands are executed as soon as they are read, so a parent process can drive an
+    // interactive session over pipes.
+    let (input, path): (Box<dyn BufRead>, _) = if args.path == Path::new("-") {
+        (Box::new(io::stdin().lock()), "<stdin>".to_string())
+    } else {
+        match File::open(&args.path) {
+            Ok(file) => (
+                Box::new(BufReader::new(file)),
+                args.path.display().to_string(),
+            ),
+            Err(error) => unreadable(&args.path, error),
+        }
+    };
+    let mut context = Context::new(SolverOptions {
+        solver: args.solver,
+        heuristic: args.heuristic,
+        restart: args.restart,
+        proof: args.proof.map(|path| (path, args.proof_format)),
+        cnf: args.cnf,
+        check_model: args.check_model,
+        error_behavior: args.error_behavior,
+    });
+    context.run(input, &path);
 
     //     assert!(matches!(commands[..], [
     //     concrete::Command::Echo {..},
@@ -45,3 +47,37 @@ fn main() {
     // ]));
     //     assert_eq!(commands[0].to_string(), "(echo \"Hello world!\")");
 }
+
+fn unreadable(path: &Path, error: io::Error) -> ! {
+    fail(&format!("{}: {}", path.display(), error))
+}
+
+/// Answers `message` with an `(error ...)` response and exits.
+fn fail(message: &str) -> ! {
+    println!("(error \"{}\")", message.replace('"', "\"\""));
+    std::process::exit(1);
+}
+
+/// Checks the proof given with `--proof` against the DIMACS CNF `cnf`.
+fn check_proof(args: &Cli, cnf: &[u8]) {
+    let Some(path) = &args.proof else {
+        fail("CHECK needs a --proof");
+    };
+    let proof = fs::read(path).unwrap_or_else(|error| unreadable(path, error));
+    let cnf = match parse_dimacs(&String::from_utf8_lossy(cnf)) {
+        Ok(cnf) => cnf,
+        Err(failure) => {
+            println!("invalid CNF: {}", failure);
+            return;
+        }
+    };
+    let result = if args.lrat {
+        check_lrat(&cnf, &proof, args.proof_format)
+    } else {
+        check_drat(&cnf, &proof, args.proof_format)
+    };
+    match result {
+        Ok(()) => println!("verified"),
+        Err(failure) => println!("not verified: {}", failure),
+    }
+}
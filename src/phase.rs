@@ -0,0 +1,124 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const REPHASE_INTERVAL: u64 = 1000;
+
+/// How the saved phases are reset at a rephase.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Rephase {
+    /// The initial polarity, false.
+    Original,
+    Inverted,
+    Random,
+    /// The phases of the longest conflict-free trail since the last rephase.
+    Best,
+}
+
+const SCHEDULE: [Rephase; 6] = [
+    Rephase::Original,
+    Rephase::Best,
+    Rephase::Inverted,
+    Rephase::Best,
+    Rephase::Random,
+    Rephase::Best,
+];
+
+/// Decision polarities: phase saving plus CaDiCaL-style target and best phases.
+pub struct Phases {
+    saved: Vec<bool>,
+    target: Vec<Option<bool>>,
+    target_len: usize,
+    best: Vec<Option<bool>>,
+    best_len: usize,
+    conflicts: u64,
+    next_rephase: u64,
+    rephases: u64,
+    rng: StdRng,
+}
+
+impl Phases {
+    pub fn new(variables: usize) -> Self {
+        Self {
+            saved: vec![false; variables],
+            target: vec![None; variables],
+            target_len: 0,
+            best: vec![None; variables],
+            best_len: 0,
+            conflicts: 0,
+            next_rephase: REPHASE_INTERVAL,
+            rephases: 0,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    pub fn add_variable(&mut self) {
+        self.saved.push(false);
+        self.target.push(None);
+        self.best.push(None);
+    }
+
+    /// The polarity of a decision on `variable`: its target phase if any, else its saved phase.
+    pub fn decide(&self, variable: usize) -> bool {
+        self.target[variable].unwrap_or(self.saved[variable])
+    }
+
+    pub fn save(&mut self, variable: usize, value: bool) {
+        self.saved[variable] = value;
+    }
+
+    /// Records a trail known to be free of conflicts; `trail` yields `(variable, value)`.
+    pub fn update(&mut self, len: usize, trail: impl Iterator<Item = (usize, bool)> + Clone) {
+        if len > self.target_len {
+            self.target.iter_mut().for_each(|p| *p = None);
+            trail
+                .clone()
+                .for_each(|(v, value)| self.target[v] = Some(value));
+            self.target_len = len;
+        }
+        if len > self.best_len {
+            self.best.iter_mut().for_each(|p| *p = None);
+            trail.for_each(|(v, value)| self.best[v] = Some(value));
+            self.best_len = len;
+        }
+    }
+
+    pub fn on_conflict(&mut self) {
+        self.conflicts += 1;
+    }
+
+    /// The target phases only track the current restart interval.
+    pub fn on_restart(&mut self) {
+        self.target_len = 0;
+    }
+
+    pub fn should_rephase(&self) -> bool {
+        self.conflicts >= self.next_rephase
+    }
+
+    pub fn rephase(&mut self) -> Rephase {
+        let rephase = SCHEDULE[self.rephases as usize % SCHEDULE.len()];
+        match rephase {
+            Rephase::Original => self.saved.iter_mut().for_each(|p| *p = false),
+            Rephase::Inverted => self.saved.iter_mut().for_each(|p| *p = true),
+            Rephase::Random => {
+                let rng = &mut self.rng;
+                self.saved.iter_mut().for_each(|p| *p = rng.gen())
+            }
+            Rephase::Best => self
+                .saved
+                .iter_mut()
+                .zip(self.best.iter())
+                .for_each(|(p, best)| *p = best.unwrap_or(*p)),
+        }
+        self.target.iter_mut().for_each(|p| *p = None);
+        self.target_len = 0;
+        self.best_len = 0;
+        self.rephases += 1;
+        self.next_rephase = self.conflicts + REPHASE_INTERVAL * (self.rephases + 1);
+        rephase
+    }
+
+    pub fn rephases(&self) -> u64 {
+        self.rephases
+    }
+}
@@ -0,0 +1,100 @@
+use crate::assertion_set::{Clause, Literal};
+use std::io::Write;
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(EnumString, AsRefStr, Copy, Clone)]
+pub enum ProofFormat {
+    TEXT,
+    BINARY,
+}
+
+/// DRAT proof writer. Literals are written with their external ids as variables,
+/// so the proof refers to the clauses of `Context::get_clauses`.
+/// The default writer is disabled and ignores every step.
+/// A failed write disables the writer, and the error is kept for `take_error`.
+#[derive(Default)]
+pub struct Proof {
+    output: Option<(Box<dyn Write>, ProofFormat)>,
+    error: Option<std::io::Error>,
+}
+
+impl Proof {
+    pub fn new(output: Box<dyn Write>, format: ProofFormat) -> Self {
+        Self {
+            output: Some((output, format)),
+            error: None,
+        }
+    }
+
+    /// The error that stopped the proof, if any, reported once.
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+
+    /// Logs a clause implied by the current clauses.
+    pub fn add(&mut self, literals: impl IntoIterator<Item = Literal>) {
+        self.write(b'a', "", literals);
+    }
+
+    /// Logs the empty clause, which concludes a refutation.
+    pub fn add_empty(&mut self) {
+        self.add(vec![]);
+    }
+
+    pub fn delete(&mut self, literals: impl IntoIterator<Item = Literal>) {
+        self.write(b'd', "d ", literals);
+    }
+
+    fn write(&mut self, tag: u8, prefix: &str, literals: impl IntoIterator<Item = Literal>) {
+        let Some((output, format)) = &mut self.output else {
+            return;
+        };
+        let mut bytes = vec![];
+        match format {
+            ProofFormat::TEXT => {
+                bytes.extend_from_slice(prefix.as_bytes());
+                for l in literals {
+                    let sign = if l.value { "" } else { "-" };
+                    bytes.extend_from_slice(format!("{}{} ", sign, l.id).as_bytes());
+                }
+                bytes.extend_from_slice(b"0\n");
+            }
+            ProofFormat::BINARY => {
+                bytes.push(tag);
+                for l in literals {
+                    // 2 * variable + sign, in 7-bit groups, least significant first.
+                    let mut encoded = 2 * l.id + !l.value as usize;
+                    while encoded > 0x7f {
+                        bytes.push(encoded as u8 & 0x7f | 0x80);
+                        encoded >>= 7;
+                    }
+                    bytes.push(encoded as u8);
+                }
+                bytes.push(0);
+            }
+        }
+        if let Err(error) = output.write_all(&bytes) {
+            self.output = None;
+            self.error = Some(error);
+        }
+    }
+}
+
+/// Writes `clauses` in DIMACS CNF, the formula a proof from `CDCLSolver::with_proof` refers to.
+pub fn write_dimacs(clauses: &[Clause], output: &mut impl Write) -> std::io::Result<()> {
+    let variables = clauses
+        .iter()
+        .flat_map(|c| c.literals.iter())
+        .map(|l| l.id)
+        .max()
+        .unwrap_or(0);
+    writeln!(output, "p cnf {} {}", variables, clauses.len())?;
+    for clause in clauses {
+        for l in &clause.literals {
+            let sign = if l.value { "" } else { "-" };
+            write!(output, "{}{} ", sign, l.id)?;
+        }
+        writeln!(output, "0")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use crate::error::RusmtError;
+use smt2parser::concrete::{Command, SyntaxBuilder};
+use smt2parser::CommandStream;
+use std::io::BufRead;
+use std::ops::Range;
+
+/// Splits its input into commands, without reading past the closing parenthesis of each,
+/// so that a driver gets the response to a command before sending the next one.
+/// A malformed command is thus skipped as a whole.
+pub struct CommandReader<R> {
+    reader: R,
+    line: usize,
+}
+
+/// The text of one command and the line where it starts.
+pub struct CommandText {
+    pub line: usize,
+    text: Vec<u8>,
+}
+
+impl<R: BufRead> CommandReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: 1 }
+    }
+
+    fn peek(&mut self) -> std::io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn consume(&mut self, byte: u8) {
+        if byte == b'\n' {
+            self.line += 1;
+        }
+        self.reader.consume(1);
+    }
+
+    /// Skips whitespace and comments up to the next command, if any.
+    fn skip_blanks(&mut self) -> std::io::Result<Option<u8>> {
+        let mut in_comment = false;
+        while let Some(byte) = self.peek()? {
+            match byte {
+                b'\n' => in_comment = false,
+                b';' => in_comment = true,
+                b' ' | b'\t' | b'\r' => {}
+                _ if !in_comment => return Ok(Some(byte)),
+                _ => {}
+            }
+            self.consume(byte);
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for CommandReader<R> {
+    type Item = std::io::Result<CommandText>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.skip_blanks() {
+            Ok(first) => first?,
+            Err(error) => return Some(Err(error)),
+        };
+        let mut command = CommandText {
+            line: self.line,
+            text: vec![],
+        };
+        // A command is an S-expression. Anything else is read up to the next delimiter.
+        let mut depth = 0;
+        let mut in_comment = false;
+        // Whether in a string literal or a quoted symbol, closed by this byte.
+        let mut quote = None;
+        loop {
+            let byte = match self.peek() {
+                Ok(Some(byte)) => byte,
+                Ok(None) => break,
+                Err(error) => return Some(Err(error)),
+            };
+            if first != b'(' && quote.is_none() && is_delimiter(byte) && !command.text.is_empty() {
+                break;
+            }
+            self.consume(byte);
+            command.text.push(byte);
+            match (quote, byte) {
+                (Some(end), _) if byte == end => quote = None,
+                (Some(_), _) => {}
+                (None, b'\n') => in_comment = false,
+                (None, _) if in_comment => {}
+                (None, b';') => in_comment = true,
+                // Quotes in string literals are escaped by doubling them, which closes the
+                // literal and opens it again.
+                (None, b'"' | b'|') => quote = Some(byte),
+                (None, b'(') => depth += 1,
+                (None, b')') => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        break;
+                    }
+                }
+                (None, _) => {}
+            }
+        }
+        Some(Ok(command))
+    }
+}
+
+impl CommandText {
+    /// Parses the command, or returns the line of the error and the error.
+    /// `(get-value (t ...))` is read as smt2parser expects it, without the parentheses
+    /// around the terms.
+    pub fn parse(&self) -> Result<Command, (usize, RusmtError)> {
+        let tokens = tokens(&self.text);
+        let mut text = self.text.clone();
+        if let Some((open, close)) = value_terms(&text, &tokens) {
+            // Blanking the parentheses keeps the offsets of the other tokens.
+            text[open] = b' ';
+            text[close] = b' ';
+        }
+        let mut stream = CommandStream::new(&text[..], SyntaxBuilder, None);
+        // The end of the last token smt2parser read.
+        let (end, message) = match stream.next() {
+            Some(Ok(command)) => return Ok(command),
+            Some(Err(smt2parser::Error::SyntaxError(position, message)))
+            | Some(Err(smt2parser::Error::ParsingError(position, message))) => {
+                (offset(&text, position.line, position.column), message)
+            }
+            None => (0, "unexpected end of input".to_string()),
+        };
+        let token = |token: &Range<usize>| String::from_utf8_lossy(&self.text[token.clone()]);
+        // smt2parser prints tokens with `Debug`: print their text instead.
+        let (start, message) = if message.starts_with("unexpected token") {
+            match tokens.iter().find(|t| t.end == end) {
+                Some(t) => (t.start, format!("unexpected token {}", token(t))),
+                None => (end, "unexpected token".to_string()),
+            }
+        } else if message == "unexpected end of input" {
+            // The lexer also stops silently at an invalid token.
+            match tokens.iter().find(|t| t.start >= end) {
+                Some(t) => (t.start, format!("invalid token {}", token(t))),
+                None => (end, message),
+            }
+        } else {
+            (end, message)
+        };
+        let line = self.line + text[..start].iter().filter(|&&b| b == b'\n').count();
+        Err((line, RusmtError::Parse(message)))
+    }
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b' ' | b'\t' | b'\r' | b'\n' | b'(' | b')' | b'"' | b'|' | b';'
+    )
+}
+
+/// The ranges of the tokens of `text`, as split by smt2parser.
+fn tokens(text: &[u8]) -> Vec<Range<usize>> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < text.len() {
+        let start = i;
+        match text[i] {
+            b';' => {
+                while i < text.len() && text[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                i += 1;
+                continue;
+            }
+            b'(' | b')' => i += 1,
+            b'"' => {
+                i += 1;
+                while i < text.len() {
+                    i += 1;
+                    if text[i - 1] == b'"' {
+                        if text.get(i) != Some(&b'"') {
+                            break;
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            b'|' => {
+                i += 1;
+                while i < text.len() && text[i] != b'|' {
+                    i += 1;
+                }
+                i = (i + 1).min(text.len());
+            }
+            _ => {
+                while i < text.len() && !is_delimiter(text[i]) {
+                    i += 1;
+                }
+            }
+        }
+        tokens.push(start..i);
+    }
+    tokens
+}
+
+/// The offsets of the parentheses around the terms of `(get-value (t ...))`.
+fn value_terms(text: &[u8], tokens: &[Range<usize>]) -> Option<(usize, usize)> {
+    let token = |i: usize| tokens.get(i).map(|range| &text[range.clone()]);
+    if token(0)? != b"(" || token(1)? != b"get-value" || token(2)? != b"(" {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, range) in tokens.iter().enumerate().skip(2) {
+        match &text[range.clone()] {
+            b"(" => depth += 1,
+            b")" => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return (i + 2 == tokens.len() && token(i + 1)? == b")")
+                .then_some((tokens[2].start, range.start));
+        }
+    }
+    None
+}
+
+/// The offset in `text` of a position of smt2parser, from 1.
+fn offset(text: &[u8], line: usize, column: usize) -> usize {
+    let start = match line {
+        1 => 0,
+        _ => text
+            .iter()
+            .enumerate()
+            .filter(|(_, &byte)| byte == b'\n')
+            .nth(line - 2)
+            .map_or(text.len(), |(i, _)| i + 1),
+    };
+    start + column - 1
+}
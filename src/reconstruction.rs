@@ -0,0 +1,51 @@
+use crate::assertion_set::Literal;
+use std::collections::HashMap;
+
+/// A preprocessing step to undo on a model of the preprocessed clauses.
+enum Step {
+    /// `clause` was removed; a model falsifying it is repaired by making `witness` true.
+    Removed {
+        clause: Vec<Literal>,
+        witness: Literal,
+    },
+    /// The variable `id` no longer occurs in any clause, so any value extends the model.
+    Eliminated(usize),
+}
+
+/// The preprocessing steps of `CDCLSolver`, in order. Variables are external ids:
+/// the renaming to solver variables is undone before the stack is applied.
+#[derive(Default)]
+pub struct Reconstruction {
+    steps: Vec<Step>,
+}
+
+impl Reconstruction {
+    /// Records a unit clause removed after fixing its variable.
+    pub fn push_unit(&mut self, literal: Literal) {
+        self.steps.push(Step::Removed {
+            clause: vec![literal],
+            witness: literal,
+        });
+    }
+
+    pub fn push_eliminated(&mut self, id: usize) {
+        self.steps.push(Step::Eliminated(id));
+    }
+
+    /// Extends a model of the preprocessed clauses to a model of the original ones,
+    /// undoing the steps from the most recent one.
+    pub fn extend(&self, model: &mut HashMap<usize, bool>) {
+        for step in self.steps.iter().rev() {
+            match step {
+                Step::Removed { clause, witness } => {
+                    if !clause.iter().any(|l| model.get(&l.id) == Some(&l.value)) {
+                        model.insert(witness.id, witness.value);
+                    }
+                }
+                Step::Eliminated(id) => {
+                    model.entry(*id).or_insert(false);
+                }
+            }
+        }
+    }
+}
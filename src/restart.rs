@@ -0,0 +1,124 @@
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(EnumString, AsRefStr, Copy, Clone)]
+pub enum RestartPolicy {
+    NONE,
+    LUBY,
+    GEOMETRIC,
+    GLUCOSE,
+}
+
+const LUBY_UNIT: u64 = 100;
+const GEOMETRIC_FIRST: f64 = 100.;
+const GEOMETRIC_FACTOR: f64 = 1.5;
+const GLUCOSE_MARGIN: f64 = 1.25;
+const GLUCOSE_MIN_CONFLICTS: u64 = 50;
+
+/// The i-th element (1-based) of the Luby sequence 1, 1, 2, 1, 1, 2, 4, ...
+pub fn luby(i: u64) -> u64 {
+    let mut k = 1;
+    while (1 << k) - 1 < i {
+        k += 1;
+    }
+    if (1 << k) - 1 == i {
+        1 << (k - 1)
+    } else {
+        luby(i - (1 << (k - 1)) + 1)
+    }
+}
+
+/// Exponential moving average, corrected for its zero initialization.
+struct Ema {
+    biased: f64,
+    alpha: f64,
+    exp: f64,
+}
+
+impl Ema {
+    fn new(alpha: f64) -> Self {
+        Self {
+            biased: 0.,
+            alpha,
+            exp: 1.,
+        }
+    }
+
+    fn update(&mut self, value: f64) {
+        self.biased += self.alpha * (value - self.biased);
+        self.exp *= 1. - self.alpha;
+    }
+
+    fn value(&self) -> f64 {
+        if self.exp == 1. {
+            0.
+        } else {
+            self.biased / (1. - self.exp)
+        }
+    }
+}
+
+/// Decides when the CDCL search should backtrack to level 0.
+pub struct Restarter {
+    policy: RestartPolicy,
+    conflicts: u64,
+    limit: u64,
+    restarts: u64,
+    fast_lbd: Ema,
+    slow_lbd: Ema,
+}
+
+impl Restarter {
+    pub fn new(policy: RestartPolicy) -> Self {
+        let mut restarter = Self {
+            policy,
+            conflicts: 0,
+            limit: 0,
+            restarts: 0,
+            fast_lbd: Ema::new(1. / 32.),
+            slow_lbd: Ema::new(1. / 4096.),
+        };
+        restarter.limit = restarter.next_limit();
+        restarter
+    }
+
+    fn next_limit(&self) -> u64 {
+        match self.policy {
+            RestartPolicy::NONE => u64::MAX,
+            RestartPolicy::LUBY => LUBY_UNIT * luby(self.restarts + 1),
+            RestartPolicy::GEOMETRIC => {
+                (GEOMETRIC_FIRST * GEOMETRIC_FACTOR.powi(self.restarts as i32)) as u64
+            }
+            RestartPolicy::GLUCOSE => GLUCOSE_MIN_CONFLICTS,
+        }
+    }
+
+    /// Records a conflict and the LBD of the clause learned from it.
+    pub fn on_conflict(&mut self, lbd: usize) {
+        self.conflicts += 1;
+        self.fast_lbd.update(lbd as f64);
+        self.slow_lbd.update(lbd as f64);
+    }
+
+    pub fn should_restart(&self) -> bool {
+        if self.conflicts < self.limit {
+            return false;
+        }
+        match self.policy {
+            // Restart while recent clauses are clearly worse than the long-run average.
+            RestartPolicy::GLUCOSE => {
+                self.fast_lbd.value() > GLUCOSE_MARGIN * self.slow_lbd.value()
+            }
+            _ => true,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.restarts += 1;
+        self.conflicts = 0;
+        self.limit = self.next_limit();
+    }
+
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+}
@@ -1,4 +1,10 @@
 use crate::assertion_set::{Clause, Literal};
+use crate::clause_db::{ClauseArena, ClauseRef, LearnedClauses, Lit};
+use crate::heuristic::{BranchingHeuristic, Heuristic};
+use crate::phase::Phases;
+use crate::proof::Proof;
+use crate::reconstruction::Reconstruction;
+use crate::restart::{RestartPolicy, Restarter};
 use crate::solver::Res::{SAT, UNSAT};
 use itertools::Itertools;
 use log::info;
@@ -31,7 +37,7 @@ pub enum Res {
 
 impl SATSolver {
     pub fn solve(&mut self) -> Res {
-        return self.solve_i(0);
+        self.solve_i(0)
     }
 
     pub fn solve_i(&mut self, cur: usize) -> Res {
@@ -41,19 +47,17 @@ impl SATSolver {
         self.assignments[cur] = Some(false);
         let no_conflict = self.clauses.iter().all(|c| self.check_clause(c));
         let next = cur + 1;
-        let res = if no_conflict && self.solve_i(next) == SAT {
-            SAT
-        } else {
-            self.assignments[cur] = Some(true);
-            let no_conflict = self.clauses.iter().all(|c| self.check_clause(c));
-            if no_conflict {
-                self.solve_i(next)
-            } else {
-                UNSAT
-            }
-        };
+        if no_conflict && self.solve_i(next) == SAT {
+            return SAT;
+        }
+        self.assignments[cur] = Some(true);
+        let no_conflict = self.clauses.iter().all(|c| self.check_clause(c));
+        if no_conflict && self.solve_i(next) == SAT {
+            return SAT;
+        }
+        // Keep the model of a SAT answer, undo anything else.
         self.assignments[cur] = None;
-        res
+        UNSAT
     }
 
     pub fn check_clause(&self, clause: &Clause) -> bool {
@@ -77,8 +81,7 @@ impl SATSolver {
 pub fn rename(mut clauses: Vec<Clause>) -> (Vec<usize>, Vec<Clause>) {
     let mut ids: Vec<_> = clauses
         .iter()
-        .map(|c| c.literals.iter())
-        .flatten()
+        .flat_map(|c| c.literals.iter())
         .map(|l| l.id)
         .unique()
         .collect();
@@ -94,7 +97,7 @@ pub fn rename(mut clauses: Vec<Clause>) -> (Vec<usize>, Vec<Clause>) {
             .literals
             .iter()
             .map(|l| {
-                let mut new_l = l.clone();
+                let mut new_l = *l;
                 new_l.id = id_to_rank[&l.id];
                 new_l
             })
@@ -104,35 +107,51 @@ pub fn rename(mut clauses: Vec<Clause>) -> (Vec<usize>, Vec<Clause>) {
     (ids, clauses)
 }
 
-#[derive(Clone, PartialEq, Debug, Hash, Eq)]
-struct Assignment {
-    value: bool,
-    clause: Option<usize>,
-    decision_level: usize,
-}
-
-impl Assignment {
-    pub fn new(value: bool, clause: Option<usize>, decision_level: usize) -> Self {
-        Self {
-            value,
-            clause,
-            decision_level,
-        }
-    }
-
-    pub fn is_decision_node(&self) -> bool {
-        self.clause.is_none()
+/// `literal` with the external id of its variable.
+fn external(ids: &[usize], literal: Lit) -> Literal {
+    Literal {
+        value: literal.value(),
+        id: ids[literal.variable()],
     }
 }
 
 pub struct CDCLSolver {
+    /// External id of each variable.
     ids: Vec<usize>,
-    pub clauses: Vec<Clause>,
-    assignments: HashMap<usize, Assignment>,
-    decision_nodes: Vec<usize>,
-    frequency: HashMap<Literal, usize>,
+    /// Variable of each external id.
+    variables: HashMap<usize, usize>,
+    /// Values fixed by preprocessing for ids that no clause mentions yet.
+    units: HashMap<usize, bool>,
+    /// Preprocessing steps, to extend models to every input id.
+    reconstruction: Reconstruction,
+    arena: ClauseArena,
+    original_clauses: usize,
+    /// Value of each variable, `None` while unassigned.
+    values: Vec<Option<bool>>,
+    /// Decision level of each assigned variable.
+    levels: Vec<usize>,
+    /// Parent clause of each assigned variable, `None` for decisions.
+    reasons: Vec<Option<ClauseRef>>,
+    heuristic: Box<dyn BranchingHeuristic>,
+    restarter: Restarter,
+    phases: Phases,
     res: Option<Res>,
-    watcher: HashMap<Literal, HashSet<usize>>
+    learned: LearnedClauses,
+    /// Clauses watching each literal, indexed by `Lit::index`, visited when the literal
+    /// becomes false. Clauses watch their first two literals; unit clauses are not watched.
+    watcher: Vec<Vec<ClauseRef>>,
+    /// Assigned literals in assignment order.
+    trail: Vec<Lit>,
+    /// Trail position where each decision level above 0 starts.
+    trail_lim: Vec<usize>,
+    /// Trail position of the propagation queue head: literals after it have not
+    /// had their watchers visited yet.
+    queue_head: usize,
+    /// Scratch marks for conflict analysis.
+    seen: Vec<bool>,
+    /// Assumptions responsible for the last UNSAT answer.
+    failed: Vec<Lit>,
+    proof: Proof,
 }
 
 // pub fn preprocess(clauses: Vec<Clause>, assignments:&mut Vec<Option<Assignment>>) -> Vec<Clause> {
@@ -143,12 +162,17 @@ pub struct CDCLSolver {
 // }
 
 impl CDCLSolver {
+    /// After a SAT answer, a model of every id given to the solver, sorted by id:
+    /// the assignment of the solver variables, extended through the preprocessing steps.
     pub fn get_assignments(&self) -> Vec<(usize, bool)> {
-        self.ids
+        let mut model: HashMap<_, _> = self
+            .ids
             .iter()
-            .zip(self.assignments.iter())
-            .map(|(id, (_lid, a))| (*id, a.value))
-            .collect()
+            .zip(self.values.iter())
+            .filter_map(|(id, value)| value.map(|v| (*id, v)))
+            .collect();
+        self.reconstruction.extend(&mut model);
+        model.into_iter().sorted().collect()
     }
 }
 
@@ -166,45 +190,39 @@ impl CDCLSolver {
 // }
 
 pub fn find_contradiction(clauses: &[Clause]) -> Option<Res> {
-    let mut res = if clauses
+    let set: HashSet<_> = clauses
         .iter()
-        .any(|c| c.len() == 2 && c.literals.iter().any(|l| c.literals.contains(&l.not())))
-    {
+        .filter(|c| c.len() == 1)
+        .flat_map(|c| c.literals.iter())
+        .collect();
+    if set.iter().any(|l| set.contains(&l.not())) {
         Some(UNSAT)
     } else {
         None
-    };
-    if res.is_none() {
-        let set: HashSet<_> = clauses
-            .iter()
-            .filter(|c| c.len() == 1)
-            .map(|c| c.literals.iter())
-            .flatten()
-            .collect();
-        if set.iter().any(|l| set.contains(&l.not())) {
-            res = Some(UNSAT)
-        }
     }
-    res
 }
 
-pub fn remove_unary(mut clauses: Vec<Clause>) -> (Option<Res>, Vec<Clause>) {
+/// Propagates unit clauses away. Returns the remaining clauses and the values
+/// of the removed variables. Shrunk and satisfied clauses are logged to `proof`.
+pub fn remove_unary(
+    mut clauses: Vec<Clause>,
+    proof: &mut Proof,
+) -> (Option<Res>, Vec<Clause>, HashMap<usize, bool>) {
     let mut assignments: HashMap<usize, bool> = HashMap::new();
     loop {
         let res = find_contradiction(&clauses);
         if res.is_some() {
-            return (res, vec![]);
+            return (res, vec![], assignments);
+        }
+        if clauses.iter().any(Clause::is_empty) {
+            return (Some(UNSAT), vec![], assignments);
         }
         let (unarys, non_unary): (Vec<_>, Vec<_>) = clauses.into_iter().partition(|c| c.len() == 1);
 
-        unarys
-            .iter()
-            .map(|c| c.literals.iter())
-            .flatten()
-            .for_each(|l| {
-                assert!(!assignments.contains_key(&l.id));
-                assignments.insert(l.id, l.value);
-            });
+        unarys.iter().flat_map(|c| c.literals.iter()).for_each(|l| {
+            let previous = assignments.insert(l.id, l.value);
+            assert!(previous.is_none_or(|value| value == l.value));
+        });
         clauses = non_unary
             .into_iter()
             .filter_map(|mut c| {
@@ -212,27 +230,28 @@ pub fn remove_unary(mut clauses: Vec<Clause>) -> (Option<Res>, Vec<Clause>) {
                 for l in &c.literals {
                     if let Some(&value) = assignments.get(&l.id) {
                         if l.value == value {
+                            proof.delete(c.literals.iter().copied());
                             return None;
                         }
                     } else {
                         literals.insert(*l);
                     }
                 }
-                if literals.is_empty() {
-                    None
-                } else {
-                    c.literals = literals;
-                    Some(c)
+                if literals.len() < c.len() {
+                    proof.add(literals.iter().copied());
+                    proof.delete(c.literals.iter().copied());
                 }
+                c.literals = literals;
+                Some(c)
             })
             .collect();
         if unarys.is_empty() {
-            return (res, clauses);
+            return (res, clauses, assignments);
         }
     }
 }
 
-pub fn watch_map(clauses: &Vec<Clause>) -> HashMap<Literal, HashSet<usize>> {
+pub fn watch_map(clauses: &[Clause]) -> HashMap<Literal, HashSet<usize>> {
     let mut map: HashMap<Literal, HashSet<usize>> = HashMap::new();
     clauses.iter().enumerate().for_each(|(i, c)| {
         c.literals.iter().for_each(|l| {
@@ -242,21 +261,26 @@ pub fn watch_map(clauses: &Vec<Clause>) -> HashMap<Literal, HashSet<usize>> {
     map
 }
 
+/// Shrinks every clause against the original ones. The shrunk clauses are logged
+/// to `proof` before any original is deleted, since each one is derived from the originals.
 fn minimize_cur_clauses(
     clauses: &[Clause],
     watch_map: &HashMap<Literal, HashSet<usize>>,
+    proof: &mut Proof,
 ) -> Vec<Clause> {
-    clauses
+    let minimized: Vec<_> = clauses
         .iter()
-        .filter_map(|c| {
-            let new_c = clause_minimization(c.clone(), clauses, watch_map);
-            if new_c.literals.is_empty() {
-                None
-            } else {
-                Some(new_c)
-            }
-        })
-        .collect()
+        .map(|c| clause_minimization(c.clone(), clauses, watch_map))
+        .collect();
+    let shrunk = || {
+        clauses
+            .iter()
+            .zip(minimized.iter())
+            .filter(|(original, c)| c.len() < original.len())
+    };
+    shrunk().for_each(|(_, c)| proof.add(c.literals.iter().copied()));
+    shrunk().for_each(|(original, _)| proof.delete(original.literals.iter().copied()));
+    minimized
 }
 
 fn clause_minimization(
@@ -264,258 +288,548 @@ fn clause_minimization(
     clauses: &[Clause],
     watch_map: &HashMap<Literal, HashSet<usize>>,
 ) -> Clause {
-    let mut removable = HashSet::with_capacity(clause.len());
-    for l in clause.literals.iter() {
+    // Literals are removed one at a time: each removal is a self-subsuming
+    // resolution against the clause as already shrunk.
+    let literals: Vec<_> = clause.literals.iter().copied().collect();
+    for l in literals {
         let not_l = l.not();
         if let Some(map) = watch_map.get(&not_l) {
-            for &c_index in map {
-                let c = &clauses[c_index];
-                if c.literals
+            let removable = map.iter().any(|&c_index| {
+                clauses[c_index]
+                    .literals
                     .iter()
                     .filter(|&&nl| nl != not_l)
-                    .all(|nl| clause.literals.contains(nl))
-                {
-                    removable.insert(*l);
-                    break;
-                }
+                    .all(|nl| *nl != l && clause.literals.contains(nl))
+            });
+            if removable {
+                clause.literals.remove(&l);
             }
         }
     }
-    // info!("C_len: {}; removable len: {}", clause.len(), removable.len());
-    clause.literals = clause.literals.difference(&removable).map(|x| *x).collect();
     clause
 }
 
 impl CDCLSolver {
     pub fn new(clauses: Vec<Clause>) -> Self {
-        println!("Initial clauses: {}", clauses.len());
-        let (res, mut clauses) = remove_unary(clauses);
-        println!("Clauses after removing unary: {}", clauses.len());
+        Self::with_proof(clauses, Proof::default())
+    }
+
+    /// A solver logging its DRAT proof, preprocessing included, to `proof`.
+    pub fn with_proof(clauses: Vec<Clause>, mut proof: Proof) -> Self {
+        info!("initial clauses: {}", clauses.len());
+        let input: HashSet<_> = clauses
+            .iter()
+            .flat_map(|c| c.literals.iter())
+            .map(|l| l.id)
+            .collect();
+        let (res, mut clauses, units) = remove_unary(clauses, &mut proof);
+        info!("clauses after removing unary: {}", clauses.len());
         if res.is_none() {
             let watch_list = watch_map(&clauses);
-            clauses = minimize_cur_clauses(&clauses, &watch_list);
-            println!("Clauses after minimization: {}", clauses.len());
+            clauses = minimize_cur_clauses(&clauses, &watch_list, &mut proof);
+            info!("clauses after minimization: {}", clauses.len());
         }
-        let (ids, mut clauses) = rename(clauses);
-        let len = ids.len();
-        let assignments = HashMap::with_capacity(len);
-        let mut frequency: HashMap<Literal, usize> = HashMap::with_capacity(len);
-        let watcher = watch_map(&clauses);
-        clauses
+        let res = res.or_else(|| clauses.iter().any(Clause::is_empty).then_some(UNSAT));
+        if res.is_some() {
+            proof.add_empty();
+            clauses.clear();
+        }
+        let remaining: Vec<_> = clauses
             .iter()
-            .map(|c| c.literals.iter())
-            .flatten()
-            .for_each(|l| *frequency.entry(*l).or_default() += 1);
-        // let clauses = preprocess(clauses, &mut assignments);
-        Self {
-            ids,
-            clauses,
-            assignments,
-            decision_nodes: vec![0],
-            frequency,
-            res,
-            watcher,
+            .flat_map(|c| c.literals.iter())
+            .map(|l| l.id)
+            .unique()
+            .sorted()
+            .collect();
+        let mut reconstruction = Reconstruction::default();
+        for (&id, &value) in units.iter().sorted() {
+            reconstruction.push_unit(Literal { value, id });
         }
+        input
+            .iter()
+            .filter(|id| !units.contains_key(id) && remaining.binary_search(id).is_err())
+            .sorted()
+            .for_each(|&id| reconstruction.push_eliminated(id));
+        let mut solver = Self {
+            ids: vec![],
+            variables: HashMap::new(),
+            units,
+            reconstruction,
+            arena: ClauseArena::default(),
+            original_clauses: 0,
+            values: vec![],
+            levels: vec![],
+            reasons: vec![],
+            heuristic: Heuristic::VSIDS.build(0),
+            restarter: Restarter::new(RestartPolicy::LUBY),
+            phases: Phases::new(0),
+            res,
+            learned: LearnedClauses::default(),
+            watcher: vec![],
+            trail: vec![],
+            trail_lim: vec![],
+            queue_head: 0,
+            seen: vec![],
+            failed: vec![],
+            proof,
+        };
+        remaining.into_iter().for_each(|id| {
+            solver.variable(id);
+        });
+        clauses.into_iter().for_each(|c| solver.add_clause(c));
+        solver
+    }
+
+    pub fn set_heuristic(&mut self, heuristic: Heuristic) {
+        self.heuristic = heuristic.build(self.ids.len());
+    }
+
+    pub fn set_restart_policy(&mut self, policy: RestartPolicy) {
+        self.restarter = Restarter::new(policy);
+    }
+
+    pub fn restarts(&self) -> u64 {
+        self.restarter.restarts()
+    }
+
+    pub fn rephases(&self) -> u64 {
+        self.phases.rephases()
+    }
+
+    pub fn original_clauses(&self) -> usize {
+        self.original_clauses
+    }
+
+    pub fn learned_clauses(&self) -> usize {
+        self.learned.len()
+    }
+
+    pub fn reductions(&self) -> u64 {
+        self.learned.reductions()
+    }
+
+    /// The error that stopped the proof, if writing it failed since the last call.
+    pub fn take_proof_error(&mut self) -> Option<std::io::Error> {
+        self.proof.take_error()
+    }
+
+    /// A subset of the assumptions of the last `solve_with_assumptions` that is
+    /// UNSAT together with the clauses. Empty if the clauses alone are UNSAT.
+    pub fn failed_assumptions(&self) -> Vec<Literal> {
+        self.failed
+            .iter()
+            .map(|&l| external(&self.ids, l))
+            .collect()
     }
 
     pub fn fully_assign(&self) -> bool {
-        self.assignments.len() == self.ids.len()
+        self.trail.len() == self.ids.len()
     }
 
-    pub fn get_next(&self) -> Option<Literal> {
-        let mut freq: Vec<_> = self.frequency.iter()
-            .filter(|(l, _)| !self.assignments.contains_key(&l.id))
-            .collect();
-        freq.sort_by_key(|x| (x.1, x.0.id, x.0.value));
-        if let Some((l, _)) = freq.pop() {
-                return Some(*l);
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn value(&self, literal: Lit) -> Option<bool> {
+        self.values[literal.variable()].map(|v| v == literal.value())
+    }
+
+    /// Assigns `literal` at the current decision level and queues it for propagation.
+    fn assign(&mut self, literal: Lit, clause: Option<ClauseRef>) {
+        let variable = literal.variable();
+        self.values[variable] = Some(literal.value());
+        self.levels[variable] = self.decision_level();
+        self.reasons[variable] = clause;
+        self.heuristic.on_assign(variable);
+        self.trail.push(literal);
+    }
+
+    /// Orders the literals of a new clause so that its watches come first:
+    /// unassigned or true literals, then false literals with the highest decision level.
+    fn order_watches(&self, literals: &mut [Lit]) {
+        literals.sort_by_key(|&l| match self.value(l) {
+            Some(false) => (1, usize::MAX - self.levels[l.variable()]),
+            _ => (0, 0),
+        });
+    }
+
+    fn watch(&mut self, clause: ClauseRef) {
+        if self.arena.len(clause) > 1 {
+            self.watcher[self.arena.lit(clause, 0).index()].push(clause);
+            self.watcher[self.arena.lit(clause, 1).index()].push(clause);
         }
-        None
+    }
+
+    /// The variable of the external `id`, created on first use. A variable fixed by
+    /// preprocessing gets its unit clause back when it is created.
+    fn variable(&mut self, id: usize) -> usize {
+        if let Some(&variable) = self.variables.get(&id) {
+            return variable;
+        }
+        let variable = self.ids.len();
+        self.ids.push(id);
+        self.variables.insert(id, variable);
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.seen.push(false);
+        self.watcher.extend([vec![], vec![]]);
+        self.heuristic.add_variable();
+        self.phases.add_variable();
+        if let Some(value) = self.units.remove(&id) {
+            let literal = Lit::new(variable, value);
+            let clause = self.arena.alloc(&[literal], false, 0);
+            self.assign(literal, Some(clause));
+        }
+        variable
+    }
+
+    fn lit(&mut self, literal: Literal) -> Lit {
+        Lit::new(self.variable(literal.id), literal.value)
+    }
+
+    /// Adds an original clause over external ids, at decision level 0: the model of
+    /// the last `solve` is discarded, learned clauses are kept. Satisfied clauses are
+    /// skipped and false literals dropped; unit clauses are assigned right away.
+    pub fn add_clause(&mut self, clause: Clause) {
+        self.backtrack(0);
+        self.original_clauses += 1;
+        let len = clause.len();
+        let mut literals = Vec::with_capacity(len);
+        for literal in clause.literals {
+            let literal = self.lit(literal);
+            match self.value(literal) {
+                Some(true) => return,
+                Some(false) => {}
+                None => literals.push(literal),
+            }
+        }
+        if literals.is_empty() {
+            self.proof.add_empty();
+            self.res = Some(UNSAT);
+            return;
+        }
+        if literals.len() < len {
+            self.proof
+                .add(literals.iter().map(|&l| external(&self.ids, l)));
+        }
+        let clause = self.arena.alloc(&literals, false, 0);
+        if literals.len() == 1 {
+            self.assign(literals[0], Some(clause));
+        } else {
+            self.watch(clause);
+        }
+    }
+
+    fn add_learned_clause(&mut self, mut literals: Vec<Lit>, lbd: usize) -> ClauseRef {
+        self.order_watches(&mut literals);
+        self.proof
+            .add(literals.iter().map(|&l| external(&self.ids, l)));
+        let clause = self.arena.alloc(&literals, true, lbd);
+        self.learned.push(&mut self.arena, clause);
+        self.watch(clause);
+        clause
+    }
+
+    pub fn get_next(&mut self) -> Option<Lit> {
+        let values = &self.values;
+        self.heuristic
+            .next_variable(&|v| values[v].is_some())
+            .map(|v| Lit::new(v, self.phases.decide(v)))
     }
 
     pub fn solve(&mut self) -> Res {
-        // self.minimize_cur_clause();
-        // self.clauses = self.clauses.iter().unique().collect();
+        self.solve_with_assumptions(&[])
+    }
+
+    /// Solves under `assumptions`, which are decided in order, one per decision level,
+    /// before any other variable. Being UNSAT under assumptions does not make later
+    /// calls UNSAT. Learned clauses and heuristic state carry over between calls.
+    pub fn solve_with_assumptions(&mut self, assumptions: &[Literal]) -> Res {
+        self.backtrack(0);
+        self.failed.clear();
+        let assumptions: Vec<Lit> = assumptions.iter().map(|&l| self.lit(l)).collect();
         if let Some(res) = &self.res {
             return *res;
         }
-        let mut current_decision_level = 0;
-
-        while let Some(cur) = self.get_next() {
-            let current_variable = cur.id;
-            current_decision_level += 1;
-            self.decision_nodes.push(current_variable);
-            assert_eq!(self.decision_nodes.len(), current_decision_level + 1);
-            self.assignments.insert(
-                current_variable,
-                Assignment::new(cur.value, None, current_decision_level),
-            );
-            loop {
-                if let Some(core) = self.propagation(current_decision_level) {
-                    info!("conflict: {}; len: {}", core, self.clauses.len());
-                    let mut roots = HashSet::new();
-                    let mut visited = HashSet::new();
-                    self.collect_roots(&mut roots, core, &mut visited);
-                    let conflict_clause: Vec<_> = roots
-                        .iter()
-                        .map(|&r| Literal {
-                            value: !self.assignments[&r].value,
-                            id: r,
-                        })
-                        .collect();
-                    let conflict_clause = Clause::new(conflict_clause);
-                    // conflict_clause = self.clause_minimization(conflict_clause);
-                    if !conflict_clause.is_empty() {
-                        conflict_clause
-                            .literals
-                            .iter()
-                            .for_each(|l| {
-                                *self.frequency.entry(*l).or_default() += 1;
-                                let len = self.clauses.len();
-                                self.watcher.get_mut(l).unwrap().insert(len);
-                            });
-                        self.clauses.push(conflict_clause);
-                    }
-                    let mut root_levels: Vec<_> = roots
-                        .iter()
-                        .map(|&r| self.assignments[&r].decision_level)
-                        .collect();
-                    root_levels.sort();
-                    let mut backtrack_decision_level = None;
-                    while let Some(highest_level) = root_levels.pop() {
-                        let highest_conflict_decision = self.decision_nodes[highest_level];
-                        let decision_node = self
-                            .assignments
-                            .get_mut(&highest_conflict_decision)
-                            .unwrap();
-                        if decision_node.value == cur.value {
-                            backtrack_decision_level = Some(highest_level);
-                            break;
-                        }
-                    }
-                    if let Some(back) = backtrack_decision_level {
-                        current_decision_level = back;
-                    } else {
+        loop {
+            if let Some(core) = self.propagation() {
+                info!("conflict: {:?}; learned: {}", core, self.learned.len());
+                if self.decision_level() == 0 {
+                    self.proof.add_empty();
+                    self.res = Some(UNSAT);
+                    return UNSAT;
+                }
+                let (learned, backjump_level) = self.analyze(core);
+                let lbd = self.lbd(learned.iter().copied());
+                self.restarter.on_conflict(lbd);
+                self.learned.on_conflict();
+                self.phases.on_conflict();
+                self.update_phases();
+                self.backtrack(backjump_level);
+                let asserting = learned[0];
+                let clause = self.add_learned_clause(learned, lbd);
+                self.assign(asserting, Some(clause));
+            } else if self.restarter.should_restart() {
+                self.restarter.restart();
+                self.phases.on_restart();
+                self.backtrack(0);
+            } else if self.learned.should_reduce() {
+                self.reduce_learned_clauses();
+            } else if self.phases.should_rephase() {
+                let rephase = self.phases.rephase();
+                info!("rephase: {:?}", rephase);
+            } else if let Some(&assumption) = assumptions.get(self.decision_level()) {
+                match self.value(assumption) {
+                    Some(false) => {
+                        self.analyze_final(assumption);
                         return UNSAT;
                     }
-                    let tobe_removed: Vec<_> = self
-                        .assignments
-                        .iter()
-                        .filter(|(_, a)| a.decision_level < current_decision_level)
-                        .map(|(i, _)| *i)
-                        .collect();
-                    tobe_removed.into_iter().for_each(|i| {
-                        self.assignments.remove(&i);
-                    });
-                    self.assignments.insert(
-                        self.decision_nodes[current_decision_level],
-                        Assignment {
-                            value: !cur.value,
-                            clause: None,
-                            decision_level: current_decision_level,
-                        },
-                    );
-                    self.decision_nodes = self.decision_nodes[0..=current_decision_level].to_vec();
-                } else {
-                    break;
+                    // Already implied: open an empty level to keep one assumption per level.
+                    Some(true) => self.trail_lim.push(self.trail.len()),
+                    None => {
+                        self.trail_lim.push(self.trail.len());
+                        self.assign(assumption, None);
+                    }
                 }
+            } else if let Some(cur) = self.get_next() {
+                self.trail_lim.push(self.trail.len());
+                self.assign(cur, None);
+            } else {
+                break;
             }
         }
-        // println!("{:?}", self.propagation());
-        // println!("Assignment: {}/{}; freq: {}", self.assignments.len(), self.ids.len()
-        //          , self.frequency.len());
         assert!(self.fully_assign());
         SAT
     }
 
-    pub fn collect_roots(
-        &self,
-        roots: &mut HashSet<usize>,
-        clause: usize,
-        visited: &mut HashSet<usize>,
-    ) {
-        if visited.contains(&clause) {
-            return;
+    /// First-UIP conflict analysis: resolves the conflicting clause with the reasons
+    /// of current-level literals, in reverse trail order, until a single current-level
+    /// literal remains. Returns the learned clause with its asserting literal first,
+    /// and the second-highest decision level in it as the backjump level.
+    fn analyze(&mut self, core: ClauseRef) -> (Vec<Lit>, usize) {
+        let current_decision_level = self.decision_level();
+        let mut involved = vec![];
+        let mut learned = vec![];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = core;
+        let mut resolved: Option<Lit> = None;
+        loop {
+            if self.arena.is_learned(clause) {
+                let lbd = self.lbd(self.arena.literals(clause));
+                self.learned.bump(&mut self.arena, clause, lbd);
+            }
+            for l in self.arena.literals(clause) {
+                let variable = l.variable();
+                if Some(l) == resolved || self.seen[variable] {
+                    continue;
+                }
+                let level = self.levels[variable];
+                if level > 0 {
+                    self.seen[variable] = true;
+                    involved.push(variable);
+                    if level == current_decision_level {
+                        pending += 1;
+                    } else {
+                        learned.push(l);
+                    }
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].variable()] {
+                    break;
+                }
+            }
+            let uip = self.trail[index];
+            pending -= 1;
+            if pending == 0 {
+                learned.insert(0, !uip);
+                break;
+            }
+            resolved = Some(uip);
+            clause = self.reasons[uip.variable()].unwrap();
         }
-        let next: Vec<_> = self.clauses[clause]
-            .literals
+        involved.iter().for_each(|&v| self.seen[v] = false);
+        let backjump_level = learned[1..]
             .iter()
-            .filter_map(|l| {
-                if let Some(c) = self.assignments[&l.id].clause {
-                    if c != clause {
-                        Some(c)
-                    } else {
-                        None
+            .map(|l| self.levels[l.variable()])
+            .max()
+            .unwrap_or(0);
+        self.heuristic.on_conflict(&involved);
+        (learned, backjump_level)
+    }
+
+    /// Final conflict analysis for an assumption found false: walks the trail back
+    /// from the implication of its negation and collects the assumptions it depends on.
+    fn analyze_final(&mut self, assumption: Lit) {
+        self.failed = vec![assumption];
+        self.seen[assumption.variable()] = true;
+        let start = self.trail_lim.first().copied().unwrap_or(self.trail.len());
+        for index in (start..self.trail.len()).rev() {
+            let literal = self.trail[index];
+            let variable = literal.variable();
+            if !self.seen[variable] {
+                continue;
+            }
+            match self.reasons[variable] {
+                // Every decision so far is an assumption.
+                None => self.failed.push(literal),
+                Some(clause) => {
+                    for l in self.arena.literals(clause) {
+                        if self.levels[l.variable()] > 0 {
+                            self.seen[l.variable()] = true;
+                        }
                     }
-                } else {
-                    roots.insert(l.id);
-                    None
                 }
+            }
+            self.seen[variable] = false;
+        }
+        self.seen[assumption.variable()] = false;
+    }
+
+    /// Literal block distance: the number of distinct decision levels in `literals`.
+    fn lbd(&self, literals: impl IntoIterator<Item = Lit>) -> usize {
+        literals
+            .into_iter()
+            .map(|l| self.levels[l.variable()])
+            .unique()
+            .count()
+    }
+
+    /// Offers the trail below the current level, which propagated without conflict,
+    /// as target and best phases.
+    fn update_phases(&mut self) {
+        let len = self.trail_lim.last().copied().unwrap_or(0);
+        self.phases.update(
+            len,
+            self.trail[..len].iter().map(|l| (l.variable(), l.value())),
+        );
+    }
+
+    /// Deletes the less useful learned clauses, then collects the garbage.
+    fn reduce_learned_clauses(&mut self) {
+        let locked: HashSet<_> = self
+            .trail
+            .iter()
+            .filter_map(|l| self.reasons[l.variable()])
+            .collect();
+        let deleted = self
+            .learned
+            .reduce(&mut self.arena, |c| locked.contains(&c));
+        for clause in deleted {
+            self.proof
+                .delete(self.arena.literals(clause).map(|l| external(&self.ids, l)));
+        }
+        self.collect_garbage();
+    }
+
+    /// Deletes the clauses, original or learned, satisfied at decision level 0: after
+    /// a unit retires an activation literal, every clause guarded by it, and every
+    /// clause learned from those, contains its false negation.
+    pub fn remove_satisfied(&mut self) {
+        self.backtrack(0);
+        let locked: HashSet<_> = self
+            .trail
+            .iter()
+            .filter_map(|l| self.reasons[l.variable()])
+            .collect();
+        // Unit clauses are never watched, and are locked anyway.
+        let satisfied: Vec<_> = self
+            .watcher
+            .iter()
+            .flatten()
+            .copied()
+            .unique()
+            .filter(|&c| {
+                !locked.contains(&c) && self.arena.literals(c).any(|l| self.value(l) == Some(true))
             })
             .collect();
-        visited.insert(clause);
-        next.into_iter()
-            .for_each(|n| self.collect_roots(roots, n, visited));
+        for &clause in &satisfied {
+            self.arena.delete(clause);
+            self.proof
+                .delete(self.arena.literals(clause).map(|l| external(&self.ids, l)));
+        }
+        if !satisfied.is_empty() {
+            self.collect_garbage();
+        }
     }
 
-    pub fn propagation(&mut self, current_decision_level: usize) -> Option<usize> {
-        let c_len = self.clauses.len();
-        loop {
-            for index in 0..c_len {
-                let conflict = !self.clauses[index].literals.iter().any(|i| {
-                    self.assignments
-                        .get(&i.id)
-                        .map(|a| a.value == i.value)
-                        .unwrap_or(true)
-                });
-                if conflict {
-                    return Some(index);
-                }
+    /// Compacts the arena after deletions, and relocates the reason, watch and
+    /// learned clause references.
+    fn collect_garbage(&mut self) {
+        let remap = self.arena.collect();
+        for l in self.trail.iter() {
+            if let Some(clause) = &mut self.reasons[l.variable()] {
+                *clause = remap[clause];
             }
+        }
+        self.watcher.iter_mut().for_each(|watching| {
+            watching.retain_mut(|clause| remap.get(clause).map(|&new| *clause = new).is_some())
+        });
+        self.learned.relocate(&remap);
+    }
 
-            let units: Vec<_> = self
-                .clauses
-                .iter()
-                .enumerate()
-                // .par_bridge()
-                .filter_map(|(i, c)| {
-                    let unresolved: Vec<_> = c
-                        .literals
-                        .iter()
-                        .filter(|l| !self.assignments.contains_key(&l.id))
-                        .collect();
-
-                    let diff: Vec<_> = c
-                        .literals
-                        .iter()
-                        .filter_map(|l| self.assignments.get(&l.id).filter(|a| a.value != l.value))
-                        .unique()
-                        .collect();
-                    // .filter(|l| l.value != ).collect();
-                    if unresolved.len() == 1 && diff.len() + 1 == c.len() {
-                        Some((*unresolved[0], i))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            if units.is_empty() {
-                return None;
-            }
+    /// Undoes every assignment above `decision_level` by truncating the trail.
+    fn backtrack(&mut self, decision_level: usize) {
+        if self.decision_level() <= decision_level {
+            return;
+        }
+        let start = self.trail_lim[decision_level];
+        for l in self.trail.drain(start..) {
+            let variable = l.variable();
+            self.values[variable] = None;
+            self.reasons[variable] = None;
+            self.phases.save(variable, l.value());
+            self.heuristic.on_unassign(variable);
+        }
+        self.trail_lim.truncate(decision_level);
+        self.queue_head = self.queue_head.min(start);
+    }
 
-            units.into_iter()
-                // .unique()
-                .for_each(|(l, i)| {
-                // assert!(self.assignments[l.id].is_none());
-                self.assignments.insert(
-                    l.id,
-                    Assignment {
-                        value: l.value,
-                        clause: Some(i),
-                        decision_level: current_decision_level,
-                    },
-                );
-            });
+    /// Two-watched-literal BCP: drains the propagation queue, visiting only the
+    /// clauses that watch the negation of each assigned literal.
+    /// Returns the conflicting clause, if any.
+    pub fn propagation(&mut self) -> Option<ClauseRef> {
+        while self.queue_head < self.trail.len() {
+            let false_literal = !self.trail[self.queue_head];
+            self.queue_head += 1;
+            let watching = std::mem::take(&mut self.watcher[false_literal.index()]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+            for (position, &clause) in watching.iter().enumerate() {
+                // Keep the false watch second.
+                if self.arena.lit(clause, 0) == false_literal {
+                    self.arena.swap(clause, 0, 1);
+                }
+                let other = self.arena.lit(clause, 0);
+                if self.value(other) == Some(true) {
+                    kept.push(clause);
+                    continue;
+                }
+                let replacement = (2..self.arena.len(clause))
+                    .find(|&i| self.value(self.arena.lit(clause, i)) != Some(false));
+                if let Some(i) = replacement {
+                    self.arena.swap(clause, 1, i);
+                    self.watcher[self.arena.lit(clause, 1).index()].push(clause);
+                    continue;
+                }
+                kept.push(clause);
+                if self.value(other).is_none() {
+                    self.assign(other, Some(clause));
+                } else {
+                    kept.extend_from_slice(&watching[position + 1..]);
+                    conflict = Some(clause);
+                    break;
+                }
+            }
+            self.watcher[false_literal.index()] = kept;
+            if conflict.is_some() {
+                self.queue_head = self.trail.len();
+                return conflict;
+            }
         }
+        None
     }
 }
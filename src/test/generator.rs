@@ -1,3 +1,4 @@
+use crate::assertion_set::{Clause, Literal};
 use rand::Rng;
 use std::fs::File;
 
@@ -35,6 +36,22 @@ impl Generator {
         use std::io::Write;
         write!(output, "{}", total).expect("Failed to write to file.");
     }
+
+    /// Random 3-CNF over variables `1..=variables`, without going through SMT-LIB.
+    pub fn clauses(&self, rng: &mut impl Rng) -> Vec<Clause> {
+        (0..self.clauses)
+            .map(|_| {
+                Clause::new(
+                    (0..3)
+                        .map(|_| Literal {
+                            value: rng.gen(),
+                            id: rng.gen_range(1..=self.variables),
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
 }
 
 // #[test]
@@ -1,2 +1,3 @@
 mod generator;
+#[allow(clippy::module_inception)]
 mod test;
@@ -1,7 +1,22 @@
-use crate::solver::Res;
+use crate::assertion_set::{Clause, Literal};
+use crate::checker::{check_drat, check_lrat, parse_dimacs};
+use crate::cli::Solver;
+use crate::context::{Context, SolverOptions};
+use crate::error::ErrorBehavior;
+use crate::heuristic::Heuristic;
+use crate::proof::{write_dimacs, Proof, ProofFormat};
+use crate::restart::RestartPolicy;
+use crate::solver::{CDCLSolver, Res, SATSolver};
 use crate::test::generator::Generator;
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use smt2parser::concrete::Symbol;
+use smt2parser::{concrete, CommandStream};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufRead, Read, Write};
+use std::path::PathBuf;
 use std::process::Command;
 use std::time::Instant;
 
@@ -73,3 +88,826 @@ fn test() {
         panic!("Incorrect.")
     }
 }
+
+#[test]
+fn cdcl_agrees_with_brute_force() {
+    let generator = Generator {
+        variables: 12,
+        clauses: 52,
+    };
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..100 {
+        let clauses = generator.clauses(&mut rng);
+        let expected = SATSolver::new(clauses.clone()).solve();
+        for heuristic in [Heuristic::VSIDS, Heuristic::VMTF, Heuristic::LRB] {
+            for policy in [
+                RestartPolicy::NONE,
+                RestartPolicy::LUBY,
+                RestartPolicy::GEOMETRIC,
+                RestartPolicy::GLUCOSE,
+            ] {
+                let mut cdcl_solver = CDCLSolver::new(clauses.clone());
+                cdcl_solver.set_heuristic(heuristic);
+                cdcl_solver.set_restart_policy(policy);
+                assert!(
+                    cdcl_solver.solve() == expected,
+                    "{} {}",
+                    heuristic.as_ref(),
+                    policy.as_ref()
+                );
+                if expected == Res::SAT {
+                    // A model of every input variable, satisfying every input clause.
+                    let model: HashMap<_, _> = cdcl_solver.get_assignments().into_iter().collect();
+                    for clause in &clauses {
+                        assert!(clause.literals.iter().all(|l| model.contains_key(&l.id)));
+                        assert!(clause.literals.iter().any(|l| model[&l.id] == l.value));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pigeon `p` sits in hole `h` when variable `p * holes + h + 1` is true.
+fn pigeonhole(pigeons: usize, holes: usize) -> Vec<Clause> {
+    let sits = |pigeon: usize, hole: usize, value: bool| Literal {
+        value,
+        id: pigeon * holes + hole + 1,
+    };
+    let mut clauses: Vec<Clause> = (0..pigeons)
+        .map(|pigeon| Clause::new((0..holes).map(|hole| sits(pigeon, hole, true)).collect()))
+        .collect();
+    for hole in 0..holes {
+        for first in 0..pigeons {
+            for second in first + 1..pigeons {
+                clauses.push(Clause::new(vec![
+                    sits(first, hole, false),
+                    sits(second, hole, false),
+                ]));
+            }
+        }
+    }
+    clauses
+}
+
+#[test]
+fn conflict_analysis_refutes_pigeonhole() {
+    for holes in 1..=4 {
+        for pigeons in [holes, holes + 1] {
+            let expected = if pigeons > holes {
+                Res::UNSAT
+            } else {
+                Res::SAT
+            };
+            assert!(
+                CDCLSolver::new(pigeonhole(pigeons, holes)).solve() == expected,
+                "{} pigeons, {} holes",
+                pigeons,
+                holes
+            );
+        }
+    }
+}
+
+#[test]
+fn incremental_cdcl_agrees_with_brute_force() {
+    let generator = Generator {
+        variables: 12,
+        clauses: 52,
+    };
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..50 {
+        let clauses = generator.clauses(&mut rng);
+        let mut cdcl_solver = CDCLSolver::new(clauses[..20].to_vec());
+        for i in 20..clauses.len() {
+            cdcl_solver.add_clause(clauses[i].clone());
+            let expected = SATSolver::new(clauses[..=i].to_vec()).solve();
+            assert!(cdcl_solver.solve() == expected);
+        }
+        for _ in 0..10 {
+            // Ids past the generator's variables are new to the solver.
+            let assumptions: Vec<_> = (0..3)
+                .map(|_| Literal {
+                    value: rng.gen(),
+                    id: rng.gen_range(1..=generator.variables + 2),
+                })
+                .collect();
+            let mut assumed = clauses.clone();
+            assumed.extend(assumptions.iter().map(|&l| Clause::new(vec![l])));
+            let expected = SATSolver::new(assumed).solve();
+            assert!(cdcl_solver.solve_with_assumptions(&assumptions) == expected);
+            if expected == Res::UNSAT {
+                let failed = cdcl_solver.failed_assumptions();
+                assert!(failed.iter().all(|l| assumptions.contains(l)));
+                let mut core = clauses.clone();
+                core.extend(failed.into_iter().map(|l| Clause::new(vec![l])));
+                assert!(SATSolver::new(core).solve() == Res::UNSAT);
+            }
+        }
+        let expected = SATSolver::new(clauses).solve();
+        assert!(cdcl_solver.solve() == expected);
+    }
+}
+
+#[test]
+fn drat_proofs_of_unsat_answers_check() {
+    let generator = Generator {
+        variables: 12,
+        clauses: 60,
+    };
+    let mut rng = StdRng::seed_from_u64(3);
+    for i in 0..50 {
+        let clauses = generator.clauses(&mut rng);
+        let mut cnf = vec![];
+        write_dimacs(&clauses, &mut cnf).unwrap();
+        let cnf = parse_dimacs(std::str::from_utf8(&cnf).unwrap()).unwrap();
+        for format in [ProofFormat::TEXT, ProofFormat::BINARY] {
+            let path = std::env::temp_dir().join(format!("rusmt-{}.drat", format.as_ref()));
+            let proof = Proof::new(Box::new(File::create(&path).unwrap()), format);
+            let mut cdcl_solver = CDCLSolver::with_proof(clauses.clone(), proof);
+            if cdcl_solver.solve() == Res::UNSAT {
+                drop(cdcl_solver);
+                let proof = std::fs::read(&path).unwrap();
+                assert_eq!(check_drat(&cnf, &proof, format), Ok(()), "instance {}", i);
+            }
+        }
+    }
+    // `5 6 0` is only RAT on 5, its first literal in the proof, whatever order
+    // propagation leaves its literals in.
+    let cnf = parse_dimacs("p cnf 7 6\n1 2 0\n1 -2 0\n-1 2 0\n-1 -2 0\n-6 7 0\n-6 -7 0\n").unwrap();
+    let proof = b"5 6 0\n5 0\n-5 1 0\n0\n";
+    assert_eq!(check_drat(&cnf, proof, ProofFormat::TEXT), Ok(()));
+}
+
+#[test]
+fn invalid_proofs_report_the_failing_lemma() {
+    let cnf = parse_dimacs("p cnf 2 2\n1 2 0\n-1 2 0\n").unwrap();
+    let failure = check_drat(&cnf, b"-2 0\n0\n", ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 1);
+
+    let cnf = parse_dimacs("p cnf 2 4\n1 2 0\n-1 2 0\n1 -2 0\n-1 -2 0\n").unwrap();
+    let proof = b"5 1 0 1 3 0\n5 d 1 3 0\n6 0 5 2 4 0\n";
+    assert_eq!(check_lrat(&cnf, proof, ProofFormat::TEXT), Ok(()));
+    let proof = b"5 1 0 1 3 0\n6 0 5 4 0\n";
+    let failure = check_lrat(&cnf, proof, ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 2);
+    let failure = check_lrat(&cnf, b"5 1 0 1 3 0\n6 0 -1 0\n", ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 2);
+    assert_eq!(failure.message, "lemma 0: RAT hints on an empty lemma");
+
+    // A proof that ends early fails on its last line.
+    let failure = check_drat(&cnf, b"1 0\nd 1 2 0\n", ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 2);
+    let failure = check_lrat(&cnf, b"5 1 0 1 3 0\n", ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 1);
+
+    // Variables must be declared by the CNF header.
+    let failure = check_drat(&cnf, b"1 0\n1 99999999999 0\n", ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 2);
+    let failure = check_lrat(&cnf, b"5 -3 0 1 0\n", ProofFormat::TEXT).unwrap_err();
+    assert_eq!(failure.line, 1);
+    for (cnf, line) in [
+        ("p cnf 2 1\n1 3 0\n", 2),
+        ("c no header\n1 0\n", 2),
+        ("p cnf 4294967296 1\n1 0\n", 1),
+        ("p cnf 2 2\n1 2 0\n-1 2\nc end\n", 3),
+    ] {
+        let failure = parse_dimacs(cnf).err().map(|f| f.line);
+        assert_eq!(failure, Some(line), "{}", cnf);
+    }
+}
+
+/// A random Boolean term over `p0..p3`, `true` and `false`, with its truth table:
+/// bit `m` is its value under the assignment giving `pi` the value of bit `i` of `m`.
+fn formula(rng: &mut StdRng, depth: usize) -> (String, u16) {
+    if depth == 0 || rng.gen_range(0..4) == 0 {
+        return match rng.gen_range(0..10) {
+            0 => ("true".to_string(), u16::MAX),
+            1 => ("false".to_string(), 0),
+            _ => {
+                let i = rng.gen_range(0..4);
+                let table = (0..16).filter(|m| m >> i & 1 == 1).map(|m| 1 << m).sum();
+                (format!("p{}", i), table)
+            }
+        };
+    }
+    let operator = ["not", "ite", "and", "or", "=>", "xor", "=", "distinct"][rng.gen_range(0..8)];
+    let arity = match operator {
+        "not" => 1,
+        "ite" => 3,
+        _ => rng.gen_range(2..=4),
+    };
+    let (terms, x): (Vec<_>, Vec<_>) = (0..arity).map(|_| formula(rng, depth - 1)).unzip();
+    let table = match operator {
+        "not" => !x[0],
+        "ite" => x[0] & x[1] | !x[0] & x[2],
+        "and" => x.iter().fold(u16::MAX, |a, &b| a & b),
+        "or" => x.iter().fold(0, |a, &b| a | b),
+        "=>" => x.iter().rev().copied().reduce(|b, a| !a | b).unwrap(),
+        "xor" => x.iter().fold(0, |a, &b| a ^ b),
+        "=" => x
+            .windows(2)
+            .fold(u16::MAX, |a, pair| a & !(pair[0] ^ pair[1])),
+        _ => (0..arity)
+            .flat_map(|i| (i + 1..arity).map(move |j| (i, j)))
+            .fold(u16::MAX, |a, (i, j)| a & (x[i] ^ x[j])),
+    };
+    (format!("({} {})", operator, terms.join(" ")), table)
+}
+
+#[test]
+fn tseitin_encoding_agrees_with_truth_tables() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for _ in 0..200 {
+        let mut script = "(set-logic QF_UF)".to_string();
+        (0..4).for_each(|i| script += &format!(" (declare-fun p{} () Bool)", i));
+        let mut table = u16::MAX;
+        for _ in 0..rng.gen_range(1..4) {
+            let (term, x) = formula(&mut rng, 3);
+            script += &format!(" (assert {})", term);
+            table &= x;
+        }
+        let expected = if table == 0 { Res::UNSAT } else { Res::SAT };
+        let context = context(&script);
+        assert!(
+            SATSolver::new(context.get_clauses()).solve() == expected,
+            "{}",
+            script
+        );
+        let mut cdcl_solver = CDCLSolver::new(context.get_clauses());
+        assert!(cdcl_solver.solve() == expected, "{}", script);
+        if expected == Res::SAT {
+            let model = cdcl_solver.get_assignments().into_iter().collect();
+            assert!(context.check_model(&model).is_empty(), "{}", script);
+        }
+    }
+}
+
+fn context(script: &str) -> Context {
+    let stream = CommandStream::new(script.as_bytes(), concrete::SyntaxBuilder, None);
+    let mut context = Context::default();
+    context.process_commands(stream.collect::<Result<Vec<_>, _>>().unwrap());
+    context
+}
+
+#[test]
+fn check_model_reports_violated_assertions() {
+    let context = context(
+        "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (assert (=> p q))
+         (assert (and p (or q p)))",
+    );
+    let mut cdcl_solver = CDCLSolver::new(context.get_clauses());
+    assert!(cdcl_solver.solve() == Res::SAT);
+    let model: HashMap<_, _> = cdcl_solver.get_assignments().into_iter().collect();
+    assert!(context.check_model(&model).is_empty());
+    let flipped = model.into_iter().map(|(id, value)| (id, !value)).collect();
+    let violations = context.check_model(&flipped);
+    assert!(violations.contains(&"assertion (and p (or q p))".to_string()));
+    assert!(violations.iter().any(|v| v.starts_with("clause")));
+}
+
+#[test]
+fn get_model_defines_every_declared_symbol() {
+    let script = "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (declare-fun r () Bool)
+         (assert (=> p q))
+         (assert p)
+         (check-sat)";
+    let with_models = format!("(set-option :produce-models true) {}", script);
+    assert_eq!(
+        context(&with_models).get_model().unwrap(),
+        "(\n  (define-fun p () Bool true)\n  (define-fun q () Bool true)\n  (define-fun r () Bool false)\n)"
+    );
+    assert_eq!(
+        context(script).get_model().unwrap_err().to_string(),
+        "model production is not enabled"
+    );
+    let unsat = format!("{} (assert (not q)) (check-sat)", with_models);
+    assert_eq!(
+        context(&unsat).get_model().unwrap_err().to_string(),
+        "the last check-sat was not sat"
+    );
+}
+
+#[test]
+fn let_and_define_fun_encode_shared_terms_once() {
+    // Each level refers to the previous one twice: 2^50 occurrences of `p` unshared.
+    let mut chain = "(and x50 (not p))".to_string();
+    for i in (1..=50).rev() {
+        chain = format!("(let ((x{} (or x{} x{}))) {})", i, i - 1, i - 1, chain);
+    }
+    let script = format!(
+        "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (assert (let ((x0 p)) {}))",
+        chain
+    );
+    let chained = context(&script);
+    assert!(chained.get_clauses().len() < 200);
+    assert!(CDCLSolver::new(chained.get_clauses()).solve() == Res::UNSAT);
+
+    let script = "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (declare-fun r () Bool)
+         (define-fun majority ((a Bool) (b Bool) (c Bool)) Bool
+           (or (and a b) (and b c) (and a c)))
+         (define-fun p-or-q () Bool (or p q))
+         (assert (majority p q r))
+         (assert (let ((p (not p)) (q p)) (and p (not q))))
+         (assert (let ((a q)) (majority a (not a) p-or-q)))";
+    let macros = context(script);
+    let mut cdcl_solver = CDCLSolver::new(macros.get_clauses());
+    assert!(cdcl_solver.solve() == Res::SAT);
+    let model = cdcl_solver.get_assignments().into_iter().collect();
+    assert!(macros.check_model(&model).is_empty());
+    let unsat = context(&format!("{} (assert (not q))", script));
+    assert!(CDCLSolver::new(unsat.get_clauses()).solve() == Res::UNSAT);
+}
+
+#[test]
+fn equal_subterms_share_one_literal() {
+    let script = "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (declare-fun r () Bool)
+         (assert (or (and p q) (= p r)))";
+    let once = context(script).get_clauses().len();
+    let repeated = format!(
+        "{} (assert (or (= r p) (and q p))) (assert (or (= (not r) (not p)) (and q q p)))",
+        script
+    );
+    // Only the unit clauses of the two assertions are new.
+    assert_eq!(context(&repeated).get_clauses().len(), once + 2);
+}
+
+#[test]
+fn contexts_number_their_variables_independently() {
+    let script = "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (assert (or p (and p q)))";
+    let threads: Vec<_> = (0..4)
+        .map(|_| std::thread::spawn(move || context(script).get_clauses()))
+        .collect();
+    let clauses: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert!(clauses.iter().all(|c| *c == clauses[0]));
+
+    let context = context(script);
+    let variables = context.variables();
+    assert_eq!(variables.len(), 4);
+    assert_eq!(variables.name(1), Some(&Symbol("p".to_string())));
+    assert_eq!(variables.name(2), Some(&Symbol("q".to_string())));
+    assert!(variables.is_auxiliary(3) && variables.is_auxiliary(4));
+}
+
+#[test]
+fn ill_sorted_terms_are_reported_and_not_encoded() {
+    let mut context = context(
+        "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun x () Int)
+         (define-fun g ((a Bool)) Bool (and a p))
+         (assert q)
+         (assert x)
+         (assert (g p p))
+         (declare-fun p () Bool)",
+    );
+    assert_eq!(context.variables().len(), 2);
+    assert!(context.get_clauses().is_empty());
+    let errors = [
+        ("q", "unknown constant q"),
+        ("(f p)", "unknown function f"),
+        ("x", "the term x has sort Int, expected Bool"),
+        ("(and p x)", "argument 2 of and has sort Int, expected Bool"),
+        (
+            "(= p x)",
+            "the arguments of = have different sorts Bool and Int",
+        ),
+        (
+            "(not p p)",
+            "wrong number of arguments for not: expected 1, got 2",
+        ),
+        ("(g x)", "argument 1 of g has sort Int, expected Bool"),
+        (
+            "(let ((y x)) (or y p))",
+            "argument 1 of or has sort Int, expected Bool",
+        ),
+    ];
+    for (term, error) in errors {
+        let script = format!("(assert {})", term);
+        let stream = CommandStream::new(script.as_bytes(), concrete::SyntaxBuilder, None);
+        let term = match stream.collect::<Result<Vec<_>, _>>().unwrap().pop() {
+            Some(concrete::Command::Assert { term }) => term,
+            _ => unreachable!(),
+        };
+        assert_eq!(context.get_value(&[term]).unwrap_err().to_string(), error);
+    }
+    let stream = CommandStream::new("(assert (g p))".as_bytes(), concrete::SyntaxBuilder, None);
+    context.process_commands(stream.collect::<Result<Vec<_>, _>>().unwrap());
+    assert!(!context.get_clauses().is_empty());
+}
+
+#[test]
+fn commands_before_set_logic_are_rejected() {
+    let log = std::env::temp_dir().join(format!("rusmt-no-logic-{}.log", std::process::id()));
+    let commands = [
+        "(declare-fun p () Bool)",
+        "(declare-const q Bool)",
+        "(define-fun r () Bool true)",
+        "(assert true)",
+        "(push 1)",
+        "(pop 1)",
+        "(check-sat)",
+        "(check-sat-assuming (p))",
+        "(get-value (p))",
+    ];
+    for command in commands {
+        let _ = std::fs::remove_file(&log);
+        let script = format!(
+            "(set-option :diagnostic-output-channel \"{}\")\n{}",
+            log.display(),
+            command
+        );
+        let mut context = context("");
+        context.run(script.as_bytes(), "script.smt2");
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "(error \"script.smt2:2: no logic is set\")\n",
+            "{}",
+            command
+        );
+        assert!(context.result().is_none());
+    }
+    std::fs::remove_file(&log).unwrap();
+}
+
+#[test]
+fn core_symbols_cannot_be_declared_or_defined() {
+    let log = std::env::temp_dir().join(format!("rusmt-core-symbols-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    let script = format!(
+        "(set-option :diagnostic-output-channel \"{}\")
+         (set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-const true Bool)
+         (define-fun and ((x Bool) (y Bool)) Bool false)
+         (declare-fun Bool () Bool)
+         (define-fun => () Bool p)
+         (assert (and p p))
+         (check-sat)",
+        log.display()
+    );
+    let mut context = context("");
+    context.run(script.as_bytes(), "script.smt2");
+    assert_eq!(
+        std::fs::read_to_string(&log).unwrap().lines().collect_vec(),
+        [
+            "(error \"script.smt2:4: symbol true is reserved by the Core theory\")",
+            "(error \"script.smt2:5: symbol and is reserved by the Core theory\")",
+            "(error \"script.smt2:6: symbol Bool is reserved by the Core theory\")",
+            "(error \"script.smt2:7: symbol => is reserved by the Core theory\")",
+        ]
+    );
+    assert!(context.result() == Some(Res::SAT));
+    std::fs::remove_file(&log).unwrap();
+}
+
+#[test]
+fn errors_are_answered_with_their_line() {
+    let log = std::env::temp_dir().join(format!("rusmt-errors-{}.log", std::process::id()));
+    let script = format!(
+        "(set-option :diagnostic-output-channel \"{}\")
+         (assert p)
+         (set-logic QF_UF)
+         (declare-fun p () Bool)
+         ; (pop 1) on line 6
+         (pop 1)
+         (frobnicate p \"a )\"\" b\" |c )|)
+         (assert (and p
+                      q))
+         (assert p)
+         (check-sat)",
+        log.display()
+    );
+    let errors = [
+        "(error \"script.smt2:2: no logic is set\")",
+        "(error \"script.smt2:6: cannot pop 1 levels, only 0 pushed\")",
+        "(error \"script.smt2:7: unexpected token frobnicate\")",
+        "(error \"script.smt2:8: unknown constant q\")",
+    ];
+    for (behavior, reported, result) in [
+        (ErrorBehavior::ContinuedExecution, 4, Some(Res::SAT)),
+        (ErrorBehavior::ImmediateExit, 1, None),
+    ] {
+        let mut context = Context::new(SolverOptions {
+            error_behavior: behavior,
+            ..Default::default()
+        });
+        let _ = std::fs::remove_file(&log);
+        context.run(script.as_bytes(), "script.smt2");
+        let output = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(output.lines().collect_vec(), errors[..reported]);
+        assert!(context.result() == result);
+    }
+    std::fs::remove_file(&log).unwrap();
+}
+
+/// Serves one command at a time, like a driver waiting for each response, and checks
+/// that the responses to the previous commands were written to `output` before.
+struct Driver {
+    commands: Vec<(String, &'static str)>,
+    sent: usize,
+    pending: Vec<u8>,
+    output: PathBuf,
+}
+
+impl Read for Driver {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.fill_buf()?.read(buf)?;
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl BufRead for Driver {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pending.is_empty() && self.sent < self.commands.len() {
+            let responses: String = self.commands[..self.sent]
+                .iter()
+                .map(|(_, response)| *response)
+                .collect();
+            let output = std::fs::read_to_string(&self.output).unwrap_or_default();
+            assert_eq!(output, responses, "before {}", self.commands[self.sent].0);
+            self.pending = self.commands[self.sent].0.clone().into_bytes();
+            self.sent += 1;
+        }
+        Ok(&self.pending)
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pending.drain(..amount);
+    }
+}
+
+#[test]
+fn each_command_is_answered_before_the_next_is_read() {
+    let output = std::env::temp_dir().join(format!("rusmt-responses-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&output);
+    let commands = vec![
+        (
+            format!(
+                "(set-option :regular-output-channel \"{}\")",
+                output.display()
+            ),
+            "",
+        ),
+        (
+            format!(
+                "(set-option :diagnostic-output-channel \"{}\")",
+                output.display()
+            ),
+            "",
+        ),
+        (
+            "\n(set-option :print-success true)".to_string(),
+            "success\n",
+        ),
+        (" (set-logic QF_UF)".to_string(), "success\n"),
+        ("(declare-fun p () Bool)".to_string(), "success\n"),
+        // A malformed command is answered once, when its closing parenthesis is read.
+        (
+            "(frobnicate p (q))".to_string(),
+            "(error \"driver:2: unexpected token frobnicate\")\n",
+        ),
+        (
+            "(assert #)".to_string(),
+            "(error \"driver:2: invalid token #\")\n",
+        ),
+        ("(assert (not p))".to_string(), "success\n"),
+        ("(check-sat)".to_string(), "sat\n"),
+        ("(get-assertions)".to_string(), "unsupported\n"),
+        ("(echo \"done\")".to_string(), "done\n"),
+        ("(exit)".to_string(), "success\n"),
+        ("(check-sat)".to_string(), ""),
+    ];
+    let mut driver = Driver {
+        commands,
+        sent: 0,
+        pending: vec![],
+        output: output.clone(),
+    };
+    context("").run(&mut driver, "driver");
+    // Nothing is read after `exit`.
+    assert_eq!(driver.sent, driver.commands.len() - 1);
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "success\nsuccess\nsuccess\n\
+         (error \"driver:2: unexpected token frobnicate\")\n\
+         (error \"driver:2: invalid token #\")\n\
+         success\nsat\nunsupported\ndone\nsuccess\n"
+    );
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn check_sat_solves_the_current_assertion_stack() {
+    let script = "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (assert (or p q))
+         (push 1)
+         (assert (not p))
+         (assert (not q))
+         (check-sat)
+         (pop 1)
+         (check-sat)";
+    for solver in [Solver::CDCL, Solver::BRUTE] {
+        let stream = CommandStream::new(script.as_bytes(), concrete::SyntaxBuilder, None);
+        let mut commands = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        let last = commands.pop().unwrap();
+        let mut context = Context::new(SolverOptions {
+            solver,
+            ..Default::default()
+        });
+        context.process_commands(commands);
+        assert!(context.result() == Some(Res::UNSAT));
+        context.process_commands(vec![last]);
+        assert!(context.result() == Some(Res::SAT));
+    }
+}
+
+#[test]
+fn check_sat_assuming_only_assumes_for_one_call() {
+    let script = "(set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (define-fun both () Bool (and p q))
+         (assert (or p q))";
+    let commands = [
+        ("(check-sat-assuming ((not p) (not q)))", Res::UNSAT),
+        ("(check-sat)", Res::SAT),
+        ("(check-sat-assuming ((not p) both))", Res::UNSAT),
+        ("(check-sat-assuming (both))", Res::SAT),
+        ("(check-sat-assuming ((not q)))", Res::SAT),
+        // An undeclared literal is an error, which leaves the last answer.
+        ("(check-sat-assuming ((not r)))", Res::SAT),
+    ];
+    for solver in [Solver::CDCL, Solver::BRUTE] {
+        let mut context = Context::new(SolverOptions {
+            solver,
+            ..Default::default()
+        });
+        context.run(script.as_bytes(), "script.smt2");
+        for (command, expected) in commands {
+            context.run(command.as_bytes(), "script.smt2");
+            assert!(context.result() == Some(expected), "{}", command);
+        }
+    }
+}
+
+#[test]
+fn get_unsat_core_names_the_assertions_in_the_core() {
+    let output = std::env::temp_dir().join(format!("rusmt-core-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&output);
+    let script = format!(
+        "(set-option :regular-output-channel \"{0}\")
+         (set-option :diagnostic-output-channel \"{0}\")
+         (set-option :produce-unsat-cores true)
+         (set-logic QF_UF)
+         (set-option :produce-unsat-cores false)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (declare-fun r () Bool)
+         (assert (! (or p q) :named either))
+         (assert (! r :named unrelated))
+         (assert (! (not p) :named not_p))
+         (assert (! (not q) :named either))
+         (assert (! (not q) :named))
+         (assert (not q))
+         (check-sat)
+         (get-unsat-core)",
+        output.display()
+    );
+    context("").run(script.as_bytes(), "script.smt2");
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "(error \"script.smt2:5: option :produce-unsat-cores can only be set before set-logic\")
+(error \"script.smt2:12: name either already names an assertion\")
+(error \"script.smt2:13: the :named attribute needs a symbol\")
+unsat
+(either not_p)
+"
+    );
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn get_value_reads_a_parenthesized_term_list() {
+    let output = std::env::temp_dir().join(format!("rusmt-value-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&output);
+    let script = format!(
+        "(set-option :regular-output-channel \"{}\")
+         (set-option :produce-models true)
+         (set-logic QF_UF)
+         (declare-fun p () Bool)
+         (declare-fun q () Bool)
+         (define-fun f ((a Bool) (b Bool)) Bool (and a b))
+         (assert p)
+         (assert (not q))
+         (check-sat)
+         (get-value (p))
+         (get-value ((not p)))
+         (get-value ((f p q) p))
+         (get-value (p q))",
+        output.display()
+    );
+    context("").run(script.as_bytes(), "script.smt2");
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "sat
+((p true))
+(((not p) false))
+(((f p q) false) (p true))
+((p true) (q false))
+"
+    );
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn push_pop_agrees_with_a_fresh_solver() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let declarations = (0..10)
+        .map(|i| format!("(declare-fun p{} () Bool)", i))
+        .collect::<Vec<_>>()
+        .join(" ");
+    for _ in 0..20 {
+        let mut context = context(&format!("(set-logic QF_UF) {}", declarations));
+        // The assertions of each level on the stack.
+        let mut levels = vec![vec![]];
+        for _ in 0..100 {
+            let command = match rng.gen_range(0..10) {
+                0 => {
+                    levels.push(vec![]);
+                    "(push 1)".to_string()
+                }
+                1 if levels.len() > 1 => {
+                    levels.pop();
+                    "(pop 1)".to_string()
+                }
+                2 => "(check-sat)".to_string(),
+                _ => {
+                    let literals: Vec<_> = (0..3)
+                        .map(|_| match (rng.gen_range(0..10), rng.gen()) {
+                            (i, true) => format!("p{}", i),
+                            (i, false) => format!("(not p{})", i),
+                        })
+                        .collect();
+                    let assertion = format!(
+                        "(assert (or {} (or {} {})))",
+                        literals[0], literals[1], literals[2]
+                    );
+                    levels.last_mut().unwrap().push(assertion.clone());
+                    assertion
+                }
+            };
+            let stream = CommandStream::new(command.as_bytes(), concrete::SyntaxBuilder, None);
+            context.process_commands(stream.collect::<Result<Vec<_>, _>>().unwrap());
+            if command == "(check-sat)" {
+                let assertions = levels.iter().flatten().join(" ");
+                let fresh = self::context(&format!(
+                    "(set-logic QF_UF) {} {}",
+                    declarations, assertions
+                ));
+                let expected = CDCLSolver::new(fresh.get_clauses()).solve();
+                assert!(context.result() == Some(expected));
+            }
+        }
+    }
+}
+
+/// Times the CDCL solver on random 3-SAT instances near the satisfiability threshold.
+/// Run with `cargo test --release benchmark -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn benchmark() {
+    let generator = Generator {
+        variables: 175,
+        clauses: 745,
+    };
+    let mut rng = StdRng::seed_from_u64(1);
+    let instances: Vec<_> = (0..20).map(|_| generator.clauses(&mut rng)).collect();
+    let start = Instant::now();
+    let sat = instances
+        .into_iter()
+        .filter(|clauses| CDCLSolver::new(clauses.clone()).solve() == Res::SAT)
+        .count();
+    println!("{} sat / 20; {}", sat, print_duration(start));
+}
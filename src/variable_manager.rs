@@ -0,0 +1,56 @@
+use smt2parser::concrete::Symbol;
+
+/// What a variable of a `Context` stands for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Variable {
+    /// A declared symbol.
+    Symbol(Symbol),
+    /// A variable of the encoding: a Tseitin gate, the selector of a named assertion,
+    /// the activation literal of a pushed level, or `true`.
+    Auxiliary,
+}
+
+/// The variables of a `Context`. Ids are dense and start at 1, as in DIMACS.
+#[derive(Default)]
+pub struct VariableManager {
+    variables: Vec<Variable>,
+}
+
+impl VariableManager {
+    pub fn new_symbol(&mut self, symbol: Symbol) -> usize {
+        self.push(Variable::Symbol(symbol))
+    }
+
+    pub fn new_auxiliary(&mut self) -> usize {
+        self.push(Variable::Auxiliary)
+    }
+
+    fn push(&mut self, variable: Variable) -> usize {
+        self.variables.push(variable);
+        self.variables.len()
+    }
+
+    pub fn get(&self, id: usize) -> &Variable {
+        &self.variables[id - 1]
+    }
+
+    pub fn is_auxiliary(&self, id: usize) -> bool {
+        *self.get(id) == Variable::Auxiliary
+    }
+
+    /// The symbol of `id`, `None` for auxiliary variables.
+    pub fn name(&self, id: usize) -> Option<&Symbol> {
+        match self.get(id) {
+            Variable::Symbol(symbol) => Some(symbol),
+            Variable::Auxiliary => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+}